@@ -1,6 +1,8 @@
 use criterion::{black_box, Criterion, criterion_group, criterion_main};
 
 mod j12;
+mod grid;
+mod search;
 
 fn bench(c: &mut Criterion) {
     c.bench_function("bench_j12_p1", |b| b.iter(|| j12::_p1(black_box(include_str!("j12/j12.txt")))));
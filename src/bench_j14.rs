@@ -1,11 +1,25 @@
 use criterion::{black_box, Criterion, criterion_group, criterion_main};
 
 mod j14;
+mod grid;
+
+use j14::world::{generate_cave, SandWorld, StopCondition};
 
 fn bench(c: &mut Criterion) {
     c.bench_function("bench_j14_p1", |b| b.iter(|| j14::_p1(black_box(include_str!("j14/j14.txt")))));
     c.bench_function("bench_j14_p2", |b| b.iter(|| j14::_p2(black_box(include_str!("j14/j14.txt")))));
+
+    let cave = SandWorld::parse(&generate_cave(42, 1000, 400, 1500)).unwrap();
+    let floored = cave.clone().with_floor(2);
+    let mut group = c.benchmark_group("bench_j14_generated_1000x400");
+    group.sample_size(10);
+    group.bench_function("fall_off_naive", |b| b.iter(|| black_box(cave.clone()).run(StopCondition::FirstFallOff)));
+    group.bench_function("fall_off_memoized", |b| b.iter(|| black_box(cave.clone()).run_memoized(StopCondition::FirstFallOff)));
+    group.bench_function("floor_naive", |b| b.iter(|| black_box(floored.clone()).run(StopCondition::SourcesBlocked)));
+    group.bench_function("floor_memoized", |b| b.iter(|| black_box(floored.clone()).run_memoized(StopCondition::SourcesBlocked)));
+    group.bench_function("floor_counted", |b| b.iter(|| black_box(&floored).count_until_blocked()));
+    group.finish();
 }
 
 criterion_group!(benches, bench);
-criterion_main!(benches);
\ No newline at end of file
+criterion_main!(benches);
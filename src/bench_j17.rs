@@ -1,6 +1,7 @@
 use criterion::{black_box, Criterion, criterion_group, criterion_main};
 
 mod j17;
+mod cycle;
 
 fn bench(c: &mut Criterion) {
     c.bench_function("bench_j17_p1", |b| b.iter(|| j17::_p1(black_box(include_str!("j17/j17.txt")))));
@@ -1,6 +1,8 @@
 use criterion::{black_box, Criterion, criterion_group, criterion_main};
 
 mod j24;
+mod search;
+mod cycle;
 
 fn bench(c: &mut Criterion) {
     c.bench_function("bench_j24_p1", |b| b.iter(|| j24::_p1(black_box(include_str!("j24/j24.txt")))));
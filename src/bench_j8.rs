@@ -1,11 +1,19 @@
 use criterion::{black_box, Criterion, criterion_group, criterion_main};
+use rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoshiro256PlusPlus;
 
+mod grid;
 mod j8;
 
 fn bench(c: &mut Criterion) {
     c.bench_function("bench_j8_p1", |b| b.iter(|| j8::_p1(black_box(include_str!("j8/j8.txt")))));
     c.bench_function("bench_j8_p2", |b| b.iter(|| j8::_p2(black_box(include_str!("j8/j8.txt")))));
+
+    let mut rng = Xoshiro256PlusPlus::seed_from_u64(42);
+    let forest = grid::Grid::from_fn(2000, 2000, |_| rng.gen_range(0..10u8));
+    c.bench_function("bench_j8_visibility_2000x2000", |b| b.iter(|| j8::visibility(black_box(&forest), j8::ALL_DIRECTIONS)));
+    c.bench_function("bench_j8_scenic_scores_2000x2000", |b| b.iter(|| j8::scenic_scores(black_box(&forest), j8::ALL_DIRECTIONS)));
 }
 
 criterion_group!(benches, bench);
-criterion_main!(benches);
\ No newline at end of file
+criterion_main!(benches);
@@ -0,0 +1,217 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+// From step `start` on, the sequence repeats itself every `length` steps
+#[allow(unused)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Cycle {
+    pub start: usize,
+    pub length: usize,
+}
+
+#[allow(unused)]
+impl Cycle {
+    // Step before `start + length` with the same state as step `n`
+    pub fn reduce(&self, n: usize) -> usize {
+        if n < self.start { n } else { self.start + (n - self.start) % self.length }
+    }
+
+    // Whole cycles between `reduce(n)` and `n`
+    pub fn repetitions(&self, n: usize) -> usize {
+        n.saturating_sub(self.start) / self.length
+    }
+
+    // `states[i]` is the state at step `i`, known up to `start + length`
+    pub fn state_at<'a, S>(&self, states: &'a [S], n: usize) -> &'a S {
+        &states[self.reduce(n)]
+    }
+
+    // Value at step `n` of a quantity growing by the same amount every cycle,
+    // like the height of a tower, `values` known up to `start + length`
+    // included
+    pub fn extrapolate(&self, values: &[usize], n: usize) -> usize {
+        if n < values.len() {
+            return values[n];
+        }
+        let per_cycle = values[self.start + self.length] - values[self.start];
+        values[self.reduce(n)] + self.repetitions(n) * per_cycle
+    }
+}
+
+// Floyd's tortoise and hare over the states `step` goes through from
+// `initial`, two states being the same when their keys are. States are
+// stepped in place and only cloned to restart. Never returns if the
+// sequence never repeats
+#[allow(unused)]
+pub fn floyd_by_key<S: Clone, K: Eq>(initial: &S, mut step: impl FnMut(&mut S), mut key: impl FnMut(&S) -> K) -> Cycle {
+    let mut tortoise = initial.clone();
+    let mut hare = initial.clone();
+    step(&mut tortoise);
+    step(&mut hare);
+    step(&mut hare);
+    while key(&tortoise) != key(&hare) {
+        step(&mut tortoise);
+        step(&mut hare);
+        step(&mut hare);
+    }
+
+    // The hare is now a multiple of the length ahead
+    let mut tortoise = initial.clone();
+    let mut start = 0;
+    while key(&tortoise) != key(&hare) {
+        step(&mut tortoise);
+        step(&mut hare);
+        start += 1;
+    }
+
+    let mut hare = tortoise.clone();
+    step(&mut hare);
+    let mut length = 1;
+    while key(&tortoise) != key(&hare) {
+        step(&mut hare);
+        length += 1;
+    }
+    Cycle { start, length }
+}
+
+// Brent's algorithm, fewer steps than Floyd's : the tortoise teleports to the
+// hare every power of two steps until the hare meets it
+#[allow(unused)]
+pub fn brent_by_key<S: Clone, K: Eq>(initial: &S, mut step: impl FnMut(&mut S), mut key: impl FnMut(&S) -> K) -> Cycle {
+    let mut tortoise_key = key(initial);
+    let mut hare = initial.clone();
+    step(&mut hare);
+    let (mut power, mut length) = (1, 1);
+    loop {
+        let hare_key = key(&hare);
+        if hare_key == tortoise_key {
+            break;
+        }
+        if power == length {
+            tortoise_key = hare_key;
+            power *= 2;
+            length = 0;
+        }
+        step(&mut hare);
+        length += 1;
+    }
+
+    // Hare `length` steps ahead, they meet at the start of the cycle
+    let mut tortoise = initial.clone();
+    let mut hare = initial.clone();
+    for _ in 0..length {
+        step(&mut hare);
+    }
+    let mut start = 0;
+    while key(&tortoise) != key(&hare) {
+        step(&mut tortoise);
+        step(&mut hare);
+        start += 1;
+    }
+    Cycle { start, length }
+}
+
+#[allow(unused)]
+pub fn floyd<S: Clone + Eq>(initial: &S, step: impl FnMut(&mut S)) -> Cycle {
+    floyd_by_key(initial, step, S::clone)
+}
+
+#[allow(unused)]
+pub fn brent<S: Clone + Eq>(initial: &S, step: impl FnMut(&mut S)) -> Cycle {
+    brent_by_key(initial, step, S::clone)
+}
+
+// First repetition of an already computed sequence, by remembering every
+// state seen
+#[allow(unused)]
+pub fn find_repeat<S: Hash + Eq>(states: impl IntoIterator<Item=S>) -> Option<Cycle> {
+    let mut seen = HashMap::new();
+    for (idx, state) in states.into_iter().enumerate() {
+        if let Some(start) = seen.insert(state, idx) {
+            return Some(Cycle { start, length: idx - start });
+        }
+    }
+    None
+}
+
+// Hash of a part of a state, like the top rows of a tower, to be used as a
+// key : a collision would make two different states the same
+#[allow(unused)]
+pub fn fingerprint<T: Hash>(items: impl IntoIterator<Item=T>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for item in items {
+        item.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+#[cfg(test)]
+#[allow(unused)]
+mod cycle_tests {
+    #[allow(unused)]
+    use super::*;
+
+    fn next(x: &mut u64) {
+        *x = (*x * *x + 1) % 255;
+    }
+
+    // Every state until one is seen again
+    fn brute_force(mut x: u64) -> Cycle {
+        let mut states = vec![];
+        loop {
+            if let Some(start) = states.iter().position(|state| *state == x) {
+                return Cycle { start, length: states.len() - start };
+            }
+            states.push(x);
+            next(&mut x);
+        }
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_detection() {
+        for initial in 0..255 {
+            let expected = brute_force(initial);
+            assert_eq!(expected, floyd(&initial, next), "from {initial}");
+            assert_eq!(expected, brent(&initial, next), "from {initial}");
+            let states = (0..600).scan(initial, |x, _| {
+                let state = *x;
+                next(x);
+                Some(state)
+            });
+            assert_eq!(Some(expected), find_repeat(states), "from {initial}");
+        }
+        assert_eq!(Cycle { start: 0, length: 7 }, brent(&0, |x| *x = (*x + 1) % 7));
+        assert_eq!(Cycle { start: 3, length: 1 }, floyd_by_key(&0, |x| *x += 1, |x| (*x).min(3)));
+        assert_eq!(None, find_repeat(0..100));
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_extrapolation() {
+        let cycle = Cycle { start: 2, length: 3 };
+        assert_eq!(1, cycle.reduce(1));
+        assert_eq!(3, cycle.reduce(9));
+        assert_eq!(2, cycle.repetitions(9));
+        assert_eq!(&'e', cycle.state_at(&['a', 'b', 'c', 'd', 'e'], 7));
+
+        // +1, +1, then +2, +0, +3 forever
+        let values = [0, 1, 2, 4, 4, 7];
+        let mut expected = values.to_vec();
+        while expected.len() < 100 {
+            expected.push(expected[expected.len() - 3] + 5);
+        }
+        for (n, value) in expected.iter().enumerate() {
+            assert_eq!(*value, cycle.extrapolate(&values, n), "at {n}");
+        }
+        assert_eq!(5 * 333_333_333 + 2, cycle.extrapolate(&values, 1_000_000_001));
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_fingerprint() {
+        assert_eq!(fingerprint([[1, 2], [3, 4]]), fingerprint(vec![[1, 2], [3, 4]]));
+        assert_ne!(fingerprint([[1, 2], [3, 4]]), fingerprint([[3, 4], [1, 2]]));
+    }
+}
@@ -0,0 +1,256 @@
+use std::fmt::{Display, Formatter};
+use std::ops::{Index, IndexMut};
+
+// (row, col)
+pub type Point = (usize, usize);
+
+pub const NEIGHBORS_4: [(isize, isize); 4] = [(-1, 0), (0, 1), (1, 0), (0, -1)];
+pub const NEIGHBORS_8: [(isize, isize); 8] = [(-1, 0), (-1, 1), (0, 1), (1, 1), (1, 0), (1, -1), (0, -1), (-1, -1)];
+
+#[allow(unused)]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ParseGridError {
+    RaggedRow { row: usize, expected: usize, found: usize },
+    InvalidCell { row: usize, col: usize, c: char },
+}
+
+impl Display for ParseGridError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseGridError::RaggedRow { row, expected, found } =>
+                write!(f, "row {row} has {found} cells, expected {expected}"),
+            ParseGridError::InvalidCell { row, col, c } =>
+                write!(f, "invalid cell {c:?} at row {row}, col {col}"),
+        }
+    }
+}
+
+impl std::error::Error for ParseGridError {}
+
+// Row major 2D storage
+#[allow(unused)]
+#[derive(Default, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Grid<T> {
+    cells: Vec<T>,
+    rows: usize,
+    cols: usize,
+}
+
+#[allow(unused)]
+impl<T> Grid<T> {
+    pub fn new(rows: usize, cols: usize, value: T) -> Self where T: Clone {
+        Self { cells: vec![value; rows * cols], rows, cols }
+    }
+
+    pub fn from_fn(rows: usize, cols: usize, mut f: impl FnMut(Point) -> T) -> Self {
+        let mut cells = Vec::with_capacity(rows * cols);
+        for row in 0..rows {
+            for col in 0..cols {
+                cells.push(f((row, col)));
+            }
+        }
+        Self { cells, rows, cols }
+    }
+
+    pub fn from_vec(rows: usize, cols: usize, cells: Vec<T>) -> Self {
+        assert_eq!(rows * cols, cells.len(), "a {rows}x{cols} grid needs {} cells", rows * cols);
+        Self { cells, rows, cols }
+    }
+
+    // One row per line, every line must have the same length
+    pub fn parse(s: &str, mut f: impl FnMut(char) -> Option<T>) -> Result<Self, ParseGridError> {
+        let mut cells = Vec::with_capacity(s.len());
+        let mut rows = 0;
+        let mut cols = 0;
+        for (row, line) in s.lines().enumerate() {
+            let before = cells.len();
+            for (col, c) in line.chars().enumerate() {
+                cells.push(f(c).ok_or(ParseGridError::InvalidCell { row, col, c })?);
+            }
+            let found = cells.len() - before;
+            if row == 0 {
+                cols = found;
+            } else if found != cols {
+                return Err(ParseGridError::RaggedRow { row, expected: cols, found });
+            }
+            rows += 1;
+        }
+        Ok(Self { cells, rows, cols })
+    }
+
+    #[inline(always)]
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    #[inline(always)]
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    #[inline(always)]
+    pub fn in_bounds(&self, (row, col): Point) -> bool {
+        row < self.rows && col < self.cols
+    }
+
+    #[inline(always)]
+    pub fn get(&self, point: Point) -> Option<&T> {
+        if self.in_bounds(point) { Some(&self.cells[point.0 * self.cols + point.1]) } else { None }
+    }
+
+    #[inline(always)]
+    pub fn get_mut(&mut self, point: Point) -> Option<&mut T> {
+        if self.in_bounds(point) { Some(&mut self.cells[point.0 * self.cols + point.1]) } else { None }
+    }
+
+    // The point reached when moving from `point` by `(d_row, d_col)`, if still inside
+    #[inline(always)]
+    pub fn offset(&self, (row, col): Point, (d_row, d_col): (isize, isize)) -> Option<Point> {
+        let row = row.checked_add_signed(d_row)?;
+        let col = col.checked_add_signed(d_col)?;
+        if self.in_bounds((row, col)) { Some((row, col)) } else { None }
+    }
+
+    pub fn neighbors4(&self, point: Point) -> impl Iterator<Item=Point> + '_ {
+        NEIGHBORS_4.iter().filter_map(move |d| self.offset(point, *d))
+    }
+
+    pub fn neighbors8(&self, point: Point) -> impl Iterator<Item=Point> + '_ {
+        NEIGHBORS_8.iter().filter_map(move |d| self.offset(point, *d))
+    }
+
+    pub fn row(&self, row: usize) -> &[T] {
+        &self.cells[(row * self.cols)..((row + 1) * self.cols)]
+    }
+
+    pub fn row_mut(&mut self, row: usize) -> &mut [T] {
+        &mut self.cells[(row * self.cols)..((row + 1) * self.cols)]
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=&T> {
+        self.cells.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item=&mut T> {
+        self.cells.iter_mut()
+    }
+
+    pub fn points(&self) -> impl Iterator<Item=Point> {
+        let cols = self.cols;
+        (0..self.cells.len()).map(move |i| (i / cols, i % cols))
+    }
+
+    pub fn enumerate(&self) -> impl Iterator<Item=(Point, &T)> {
+        self.points().zip(self.cells.iter())
+    }
+
+    pub fn position(&self, predicate: impl FnMut(&T) -> bool) -> Option<Point> {
+        self.cells.iter().position(predicate).map(|i| (i / self.cols, i % self.cols))
+    }
+
+    pub fn map<U>(&self, f: impl FnMut(&T) -> U) -> Grid<U> {
+        Grid { cells: self.cells.iter().map(f).collect(), rows: self.rows, cols: self.cols }
+    }
+
+    // One line per row, one char per cell
+    pub fn render(&self, mut f: impl FnMut(&T) -> char) -> String {
+        let mut s = String::with_capacity((self.cols + 1) * self.rows);
+        for row in 0..self.rows {
+            s.extend(self.row(row).iter().map(&mut f));
+            s.push('\n');
+        }
+        s
+    }
+}
+
+impl<T> Index<Point> for Grid<T> {
+    type Output = T;
+
+    #[inline(always)]
+    fn index(&self, (row, col): Point) -> &Self::Output {
+        debug_assert!(col < self.cols);
+        &self.cells[row * self.cols + col]
+    }
+}
+
+impl<T> IndexMut<Point> for Grid<T> {
+    #[inline(always)]
+    fn index_mut(&mut self, (row, col): Point) -> &mut Self::Output {
+        debug_assert!(col < self.cols);
+        &mut self.cells[row * self.cols + col]
+    }
+}
+
+impl<T> Index<usize> for Grid<T> {
+    type Output = [T];
+
+    #[inline(always)]
+    fn index(&self, row: usize) -> &Self::Output {
+        self.row(row)
+    }
+}
+
+impl<T> IndexMut<usize> for Grid<T> {
+    #[inline(always)]
+    fn index_mut(&mut self, row: usize) -> &mut Self::Output {
+        self.row_mut(row)
+    }
+}
+
+impl<T: Display> Display for Grid<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for row in 0..self.rows {
+            for cell in self.row(row) {
+                write!(f, "{cell}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[allow(unused)]
+mod grid_tests {
+    #[allow(unused)]
+    use super::*;
+
+    #[test]
+    #[allow(unused)]
+    fn test_parse_and_index() {
+        let grid = Grid::parse("123\n456\n", |c| c.to_digit(10)).unwrap();
+        assert_eq!((2, 3), (grid.rows(), grid.cols()));
+        assert_eq!(6, grid[(1, 2)]);
+        assert_eq!([4, 5, 6], grid[1]);
+        assert_eq!(None, grid.get((2, 0)));
+        assert_eq!(None, grid.get((0, 3)));
+        assert_eq!(Some((1, 0)), grid.position(|c| *c == 4));
+        assert_eq!("123\n456\n", grid.to_string());
+        assert_eq!("#..\n...\n", grid.render(|c| if *c == 1 { '#' } else { '.' }));
+
+        assert_eq!(Err(ParseGridError::RaggedRow { row: 1, expected: 3, found: 2 }), Grid::parse("123\n45", |c| c.to_digit(10)));
+        assert_eq!(Err(ParseGridError::InvalidCell { row: 0, col: 1, c: 'x' }), Grid::parse("1x", |c| c.to_digit(10)));
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_neighbors() {
+        let grid = Grid::new(3, 4, 0u8);
+        assert_eq!(vec![(0, 1), (1, 0)], grid.neighbors4((0, 0)).collect::<Vec<_>>());
+        assert_eq!(vec![(0, 1), (1, 1), (1, 0)], grid.neighbors8((0, 0)).collect::<Vec<_>>());
+        assert_eq!(8, grid.neighbors8((1, 1)).count());
+        assert_eq!(vec![(1, 3), (2, 2)], grid.neighbors4((2, 3)).collect::<Vec<_>>());
+        assert_eq!(Some((2, 1)), grid.offset((0, 0), (2, 1)));
+        assert_eq!(None, grid.offset((0, 0), (-1, 0)));
+    }
+}
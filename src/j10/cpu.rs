@@ -0,0 +1,392 @@
+use std::fmt::{Display, Formatter, Write};
+use std::str::FromStr;
+
+use smallvec::SmallVec;
+
+#[allow(unused)]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Registers {
+    pub x: i32,
+}
+
+pub type Effect = fn(&mut Registers, &[i32]);
+
+#[allow(unused)]
+#[derive(Clone, Debug)]
+pub struct Opcode {
+    pub name: String,
+    pub cycles: u32,
+    pub arity: usize,
+    pub effect: Effect,
+}
+
+#[allow(unused)]
+#[derive(Clone, Debug)]
+pub struct InstructionSet {
+    opcodes: Vec<Opcode>,
+}
+
+#[allow(unused)]
+impl InstructionSet {
+    pub fn empty() -> Self {
+        Self { opcodes: vec![] }
+    }
+
+    // Replaces any opcode with the same name
+    pub fn register(&mut self, name: &str, cycles: u32, arity: usize, effect: Effect) -> &mut Self {
+        assert!(cycles > 0, "an instruction takes at least one cycle");
+        let opcode = Opcode { name: name.to_string(), cycles, arity, effect };
+        match self.opcodes.iter().position(|op| op.name == name) {
+            Some(idx) => self.opcodes[idx] = opcode,
+            None => self.opcodes.push(opcode),
+        }
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<usize> {
+        self.opcodes.iter().position(|op| op.name == name)
+    }
+
+    pub fn opcode(&self, idx: usize) -> &Opcode {
+        &self.opcodes[idx]
+    }
+}
+
+impl Default for InstructionSet {
+    fn default() -> Self {
+        let mut set = Self::empty();
+        set.register("noop", 1, 0, |_, _| {});
+        set.register("addx", 2, 1, |registers, args| registers.x += args[0]);
+        set
+    }
+}
+
+#[allow(unused)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Instruction {
+    pub opcode: usize,
+    pub args: SmallVec<[i32; 2]>,
+}
+
+#[allow(unused)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DecodeError {
+    UnknownOpcode { line: usize, opcode: String },
+    WrongArity { line: usize, opcode: String, expected: usize, found: usize },
+    InvalidArgument { line: usize, argument: String },
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::UnknownOpcode { line, opcode } =>
+                write!(f, "line {line}: unknown opcode {opcode}"),
+            DecodeError::WrongArity { line, opcode, expected, found } =>
+                write!(f, "line {line}: {opcode} takes {expected} arguments, found {found}"),
+            DecodeError::InvalidArgument { line, argument } =>
+                write!(f, "line {line}: invalid argument {argument}"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+#[allow(unused)]
+#[derive(Clone, Debug)]
+pub struct Program {
+    pub set: InstructionSet,
+    pub instructions: Vec<Instruction>,
+}
+
+#[allow(unused)]
+impl Program {
+    // Lines are 1 based in errors
+    pub fn decode(s: &str, set: InstructionSet) -> Result<Self, DecodeError> {
+        let mut instructions = vec![];
+        for (line_idx, line) in s.lines().enumerate() {
+            let line_number = line_idx + 1;
+            let mut words = line.split_whitespace();
+            let name = match words.next() {
+                None => continue,
+                Some(name) => name,
+            };
+            let opcode = set.get(name)
+                .ok_or_else(|| DecodeError::UnknownOpcode { line: line_number, opcode: name.to_string() })?;
+            let args = words
+                .map(|word| i32::from_str(word)
+                    .map_err(|_| DecodeError::InvalidArgument { line: line_number, argument: word.to_string() }))
+                .collect::<Result<SmallVec<[i32; 2]>, _>>()?;
+            let expected = set.opcode(opcode).arity;
+            if args.len() != expected {
+                return Err(DecodeError::WrongArity { line: line_number, opcode: name.to_string(), expected, found: args.len() });
+            }
+            instructions.push(Instruction { opcode, args });
+        }
+        Ok(Self { set, instructions })
+    }
+
+    pub fn format(&self, instruction: &Instruction) -> String {
+        let mut s = self.set.opcode(instruction.opcode).name.clone();
+        for arg in instruction.args.iter() {
+            write!(s, " {arg}").unwrap();
+        }
+        s
+    }
+}
+
+// What the cpu looks like during a cycle, before the running instruction
+// has completed
+#[allow(unused)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct CycleState {
+    pub cycle: usize,
+    pub pc: usize,
+    pub registers: Registers,
+}
+
+pub trait CycleObserver {
+    fn on_cycle(&mut self, state: &CycleState);
+}
+
+// Wraps a closure into an observer
+#[allow(unused)]
+pub struct FnObserver<F: FnMut(&CycleState)>(pub F);
+
+impl<F: FnMut(&CycleState)> CycleObserver for FnObserver<F> {
+    fn on_cycle(&mut self, state: &CycleState) {
+        (self.0)(state)
+    }
+}
+
+#[allow(unused)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Breakpoint {
+    // Stops before this cycle runs, the next step runs it
+    Cycle(usize),
+    // Stops as soon as an instruction sets X to this value
+    RegisterX(i32),
+}
+
+#[allow(unused)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum StopReason {
+    Halted,
+    Breakpoint(Breakpoint),
+    Cycle,
+}
+
+#[allow(unused)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TraceEntry {
+    pub state: CycleState,
+    pub instruction: String,
+    pub completed: bool,
+    pub registers_after: Registers,
+}
+
+#[allow(unused)]
+pub struct Cpu<'a> {
+    program: &'a Program,
+    registers: Registers,
+    pc: usize,
+    cycle: usize,
+    remaining: u32,
+    observers: Vec<Box<dyn CycleObserver + 'a>>,
+    breakpoints: Vec<Breakpoint>,
+    // Cycle a breakpoint stopped before, not stopped before again
+    stopped_before: Option<usize>,
+    trace: Option<Vec<TraceEntry>>,
+}
+
+#[allow(unused)]
+impl<'a> Cpu<'a> {
+    pub fn new(program: &'a Program) -> Self {
+        let mut cpu = Self {
+            program,
+            registers: Registers { x: 1 },
+            pc: 0,
+            cycle: 0,
+            remaining: 0,
+            observers: vec![],
+            breakpoints: vec![],
+            stopped_before: None,
+            trace: None,
+        };
+        cpu.load_current();
+        cpu
+    }
+
+    fn load_current(&mut self) {
+        self.remaining = self.program.instructions.get(self.pc)
+            .map_or(0, |instruction| self.program.set.opcode(instruction.opcode).cycles);
+    }
+
+    pub fn add_observer(&mut self, observer: impl CycleObserver + 'a) -> &mut Self {
+        self.observers.push(Box::new(observer));
+        self
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> &mut Self {
+        self.breakpoints.push(breakpoint);
+        self
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    pub fn enable_trace(&mut self) -> &mut Self {
+        self.trace.get_or_insert_with(Vec::new);
+        self
+    }
+
+    pub fn registers(&self) -> Registers {
+        self.registers
+    }
+
+    // Cycles completed so far
+    pub fn cycle(&self) -> usize {
+        self.cycle
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.pc >= self.program.instructions.len()
+    }
+
+    // Runs one cycle, returns the breakpoint hit at the end of it if any. A
+    // cycle breakpoint stops before the cycle instead, without running it
+    pub fn step(&mut self) -> Option<StopReason> {
+        if self.is_halted() {
+            return Some(StopReason::Halted);
+        }
+        let next = self.cycle + 1;
+        if self.stopped_before != Some(next) {
+            if let Some(breakpoint) = self.breakpoints.iter().find(|breakpoint| **breakpoint == Breakpoint::Cycle(next)) {
+                self.stopped_before = Some(next);
+                return Some(StopReason::Breakpoint(*breakpoint));
+            }
+        }
+        self.stopped_before = None;
+        self.cycle = next;
+        let state = CycleState { cycle: self.cycle, pc: self.pc, registers: self.registers };
+        for observer in self.observers.iter_mut() {
+            observer.on_cycle(&state);
+        }
+
+        self.remaining -= 1;
+        let completed = self.remaining == 0;
+        let instruction = &self.program.instructions[self.pc];
+        if completed {
+            (self.program.set.opcode(instruction.opcode).effect)(&mut self.registers, &instruction.args);
+        }
+        if let Some(trace) = self.trace.as_mut() {
+            trace.push(TraceEntry {
+                state,
+                instruction: self.program.format(instruction),
+                completed,
+                registers_after: self.registers,
+            });
+        }
+        if completed {
+            self.pc += 1;
+            self.load_current();
+        }
+
+        self.breakpoints.iter()
+            .find(|breakpoint| match breakpoint {
+                Breakpoint::Cycle(_) => false,
+                Breakpoint::RegisterX(x) => completed && state.registers.x != *x && self.registers.x == *x,
+            })
+            .map(|breakpoint| StopReason::Breakpoint(*breakpoint))
+    }
+
+    pub fn run(&mut self) -> StopReason {
+        loop {
+            if let Some(reason) = self.step() {
+                return reason;
+            }
+        }
+    }
+
+    // Runs until `cycle` cycles are completed, the program halts or a
+    // breakpoint is hit
+    pub fn run_until(&mut self, cycle: usize) -> StopReason {
+        while self.cycle < cycle {
+            if let Some(reason) = self.step() {
+                return reason;
+            }
+        }
+        StopReason::Cycle
+    }
+
+    pub fn trace(&self) -> &[TraceEntry] {
+        self.trace.as_deref().unwrap_or(&[])
+    }
+
+    pub fn trace_dump(&self) -> String {
+        let mut dump = String::new();
+        for entry in self.trace() {
+            write!(dump, "cycle {:>4} | pc {:>4} | {:<12} | X={}",
+                   entry.state.cycle, entry.state.pc, entry.instruction, entry.state.registers.x).unwrap();
+            if entry.completed && entry.registers_after != entry.state.registers {
+                write!(dump, " -> {}", entry.registers_after.x).unwrap();
+            }
+            dump.push('\n');
+        }
+        dump
+    }
+}
+
+// Sums cycle * X during cycles 20, 60, 100...
+#[allow(unused)]
+#[derive(Debug, Default)]
+pub struct SignalStrength {
+    pub total: i32,
+}
+
+impl CycleObserver for SignalStrength {
+    fn on_cycle(&mut self, state: &CycleState) {
+        if (state.cycle + 20).is_multiple_of(40) {
+            self.total += state.cycle as i32 * state.registers.x;
+        }
+    }
+}
+
+// Draws a pixel per cycle where the 3 pixels wide sprite centered on X is
+#[allow(unused)]
+#[derive(Debug)]
+pub struct Crt<const ROWS: usize, const COLS: usize> {
+    pub pixels: Vec<char>,
+}
+
+#[allow(unused)]
+impl<const ROWS: usize, const COLS: usize> Crt<ROWS, COLS> {
+    pub fn new() -> Self {
+        Self { pixels: vec!['.'; ROWS * COLS] }
+    }
+}
+
+impl<const ROWS: usize, const COLS: usize> CycleObserver for Crt<ROWS, COLS> {
+    fn on_cycle(&mut self, state: &CycleState) {
+        let position = state.cycle - 1;
+        if position >= self.pixels.len() {
+            return;
+        }
+        let col = (position % COLS) as i32;
+        let x = state.registers.x;
+        let cols = COLS as i32;
+        if col == x || col == (x - 1) % cols || col == (x + 1) % cols {
+            self.pixels[position] = '#';
+        }
+    }
+}
+
+impl<T: CycleObserver> CycleObserver for &mut T {
+    fn on_cycle(&mut self, state: &CycleState) {
+        (**self).on_cycle(state)
+    }
+}
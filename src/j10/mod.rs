@@ -1,5 +1,10 @@
 use std::fmt::{Display, Formatter, Write};
-use std::str::FromStr;
+
+use crate::j10::cpu::{Cpu, Crt, InstructionSet, Program, SignalStrength};
+use crate::j10::ocr::OcrError;
+
+pub mod cpu;
+pub mod ocr;
 
 #[derive(Debug, Eq, PartialEq)]
 pub struct P2Output([char; ROWS * COLS]);
@@ -16,6 +21,13 @@ impl Display for P2Output {
     }
 }
 
+impl P2Output {
+    #[allow(unused)]
+    pub fn read_letters(&self) -> Result<String, OcrError> {
+        ocr::read_letters(&self.0, COLS)
+    }
+}
+
 impl From<&str> for P2Output {
     fn from(s: &str) -> Self {
         let mut chars = ['.'; ROWS * COLS];
@@ -32,52 +44,19 @@ impl From<&str> for P2Output {
 
 
 #[inline(always)]
-fn increase_and_check_cycle_and_draw_pixel(cycle: &mut i32,
-                                           x: i32,
-                                           total: &mut i32,
-                                           pixels: &mut [char]) {
-    if *cycle % 40 == x ||
-        (*cycle) % 40 == (x - 1) % 40 ||
-        (*cycle) % 40 == (x + 1) % 40 {
-        pixels[(*cycle) as usize] = '#';
-    }
-    *cycle += 1;
-    if (*cycle + 20) % 40 == 0 {
-        *total += *cycle * x;
-    }
-}
-
-#[inline(always)]
-fn increase_and_check_cycle(cycle: &mut i32, x: i32, total: &mut i32) {
-    *cycle += 1;
-    if (*cycle + 20) % 40 == 0 {
-        *total += *cycle * x;
-    }
+fn decode(s: &str) -> Program {
+    Program::decode(s, InstructionSet::default()).unwrap()
 }
 
 #[allow(unused)]
 pub fn _p1(s: &str) -> usize {
-    let mut x: i32 = 1;
-
-    let mut cycles = 0i32;
-    let mut total = 0i32;
-
-    for line in s.lines() {
-        let mut words = line.split(' ');
-        let command = words.next().unwrap();
-        match command {
-            "noop" => {
-                increase_and_check_cycle(&mut cycles, x, &mut total);
-            }
-            "addx" => {
-                increase_and_check_cycle(&mut cycles, x, &mut total);
-                increase_and_check_cycle(&mut cycles, x, &mut total);
-                x += i32::from_str(words.next().unwrap()).unwrap();
-            }
-            _ => panic!(),
-        }
-    }
-    total as usize
+    let program = decode(s);
+    let mut signal_strength = SignalStrength::default();
+    let mut cpu = Cpu::new(&program);
+    cpu.add_observer(&mut signal_strength);
+    cpu.run();
+    drop(cpu);
+    signal_strength.total as usize
 }
 
 const ROWS: usize = 6usize;
@@ -90,29 +69,13 @@ pub fn p1() -> usize {
 
 #[allow(unused)]
 pub fn _p2(s: &str) -> P2Output {
-    let mut x: i32 = 1;
-
-    let mut cycles = 0i32;
-    let mut total = 0i32;
-
-    let mut pixels = ['.'; ROWS * COLS];
-
-    for line in s.lines() {
-        let mut words = line.split(' ');
-        let command = words.next().unwrap();
-        match command {
-            "noop" => {
-                increase_and_check_cycle_and_draw_pixel(&mut cycles, x, &mut total, &mut pixels);
-            }
-            "addx" => {
-                increase_and_check_cycle_and_draw_pixel(&mut cycles, x, &mut total, &mut pixels);
-                increase_and_check_cycle_and_draw_pixel(&mut cycles, x, &mut total, &mut pixels);
-                x += i32::from_str(words.next().unwrap()).unwrap();
-            }
-            _ => panic!(),
-        }
-    }
-    P2Output(pixels)
+    let program = decode(s);
+    let mut crt = Crt::<ROWS, COLS>::new();
+    let mut cpu = Cpu::new(&program);
+    cpu.add_observer(&mut crt);
+    cpu.run();
+    drop(cpu);
+    P2Output(crt.pixels.try_into().unwrap())
 }
 
 #[allow(unused)]
@@ -125,6 +88,7 @@ pub fn p2() -> P2Output {
 mod j10_tests {
     #[allow(unused)]
     use super::*;
+    use crate::j10::cpu::{Breakpoint, CycleState, DecodeError, FnObserver, StopReason};
 
     #[test]
     #[allow(unused)]
@@ -138,5 +102,90 @@ mod j10_tests {
     fn test_p2() {
         assert_eq!(P2Output::from(include_str!("j10_test_p2_sol.txt")), _p2(include_str!("j10_test.txt")));
         assert_eq!(P2Output::from(include_str!("j10_p2_sol.txt")), _p2(include_str!("j10.txt")));
+        assert_eq!(Ok("RBPARAGF".to_string()), _p2(include_str!("j10.txt")).read_letters());
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_ocr_errors() {
+        let error = _p2(include_str!("j10_test.txt")).read_letters().unwrap_err();
+        assert_eq!("????????", error.partial);
+        assert_eq!((0..8).collect::<Vec<_>>(), error.unrecognized);
+
+        let mut pixels = P2Output::from(include_str!("j10_p2_sol.txt"));
+        pixels.0[COLS + 11] = '#';
+        pixels.0[2 * COLS + 36] = '.';
+        let error = pixels.read_letters().unwrap_err();
+        assert_eq!("RB?ARAG?", error.partial);
+        assert_eq!(vec![2, 7], error.unrecognized);
+        assert_eq!("could only read \"RB?ARAG?\", unrecognized glyphs at 2 (columns 10-13), 7 (columns 35-38)", error.to_string());
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_cpu_step_and_breakpoints() {
+        let program = decode("noop\naddx 3\naddx -5\n");
+        let mut xs = vec![];
+        let mut cpu = Cpu::new(&program);
+        cpu.add_observer(FnObserver(|state: &CycleState| xs.push((state.cycle, state.registers.x))));
+        cpu.add_breakpoint(Breakpoint::RegisterX(4));
+        assert_eq!(StopReason::Breakpoint(Breakpoint::RegisterX(4)), cpu.run());
+        assert_eq!((3, 2, 4), (cpu.cycle(), cpu.pc(), cpu.registers().x));
+        assert_eq!(StopReason::Cycle, cpu.run_until(4));
+        assert_eq!(StopReason::Halted, cpu.run());
+        assert_eq!(-1, cpu.registers().x);
+        assert!(cpu.is_halted());
+        drop(cpu);
+        assert_eq!(vec![(1, 1), (2, 1), (3, 1), (4, 4), (5, 4)], xs);
+
+        let program = decode(include_str!("j10_test.txt"));
+        let mut cpu = Cpu::new(&program);
+        cpu.add_breakpoint(Breakpoint::Cycle(220));
+        assert_eq!(StopReason::Breakpoint(Breakpoint::Cycle(220)), cpu.run());
+        assert_eq!(219, cpu.cycle());
+        assert_eq!(StopReason::Cycle, cpu.run_until(219));
+        // Resuming runs the cycle stopped before
+        assert_eq!(StopReason::Cycle, cpu.run_until(220));
+        assert_eq!(220, cpu.cycle());
+
+        // Even the first cycle, or the one about to run when added
+        let mut cpu = Cpu::new(&program);
+        cpu.add_breakpoint(Breakpoint::Cycle(1));
+        assert_eq!(StopReason::Breakpoint(Breakpoint::Cycle(1)), cpu.run());
+        assert_eq!(0, cpu.cycle());
+        assert_eq!(StopReason::Cycle, cpu.run_until(10));
+        cpu.add_breakpoint(Breakpoint::Cycle(11));
+        assert_eq!(StopReason::Breakpoint(Breakpoint::Cycle(11)), cpu.step().unwrap());
+        assert_eq!(10, cpu.cycle());
+        assert_eq!(StopReason::Halted, cpu.run());
+        assert_eq!(240, cpu.cycle());
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_cpu_custom_opcodes_and_trace() {
+        let mut set = InstructionSet::default();
+        set.register("mulx", 3, 1, |registers, args| registers.x *= args[0]);
+        set.register("noop", 2, 0, |_, _| {});
+        let program = Program::decode("mulx 5\nnoop\naddx -2\n", set).unwrap();
+        let mut cpu = Cpu::new(&program);
+        cpu.enable_trace();
+        cpu.run();
+        assert_eq!((7, 3), (cpu.cycle(), cpu.registers().x));
+        assert_eq!(concat!(
+            "cycle    1 | pc    0 | mulx 5       | X=1\n",
+            "cycle    2 | pc    0 | mulx 5       | X=1\n",
+            "cycle    3 | pc    0 | mulx 5       | X=1 -> 5\n",
+            "cycle    4 | pc    1 | noop         | X=5\n",
+            "cycle    5 | pc    1 | noop         | X=5\n",
+            "cycle    6 | pc    2 | addx -2      | X=5\n",
+            "cycle    7 | pc    2 | addx -2      | X=5 -> 3\n"), cpu.trace_dump());
+
+        assert_eq!(Err(DecodeError::UnknownOpcode { line: 2, opcode: "jmp".to_string() }),
+                   Program::decode("noop\njmp 2", InstructionSet::default()).map(|_| ()));
+        assert_eq!(Err(DecodeError::WrongArity { line: 1, opcode: "addx".to_string(), expected: 1, found: 0 }),
+                   Program::decode("addx", InstructionSet::default()).map(|_| ()));
+        assert_eq!(Err(DecodeError::InvalidArgument { line: 1, argument: "a".to_string() }),
+                   Program::decode("addx a", InstructionSet::default()).map(|_| ()));
     }
 }
\ No newline at end of file
@@ -0,0 +1,83 @@
+use std::fmt::{Display, Formatter};
+
+pub const GLYPH_WIDTH: usize = 4;
+pub const GLYPH_HEIGHT: usize = 6;
+// Each glyph is followed by an empty column
+pub const GLYPH_STRIDE: usize = GLYPH_WIDTH + 1;
+
+// Letters of the puzzle font, Y left out as it is drawn 5 columns wide
+const FONT: [(char, &str); 17] = [
+    ('A', ".##.#..##..######..##..#"),
+    ('B', "###.#..####.#..##..####."),
+    ('C', ".##.#..##...#...#..#.##."),
+    ('E', "#####...###.#...#...####"),
+    ('F', "#####...###.#...#...#..."),
+    ('G', ".##.#..##...#.###..#.###"),
+    ('H', "#..##..######..##..##..#"),
+    ('I', ".###..#...#...#...#..###"),
+    ('J', "..##...#...#...##..#.##."),
+    ('K', "#..##.#.##..#.#.#.#.#..#"),
+    ('L', "#...#...#...#...#...####"),
+    ('O', ".##.#..##..##..##..#.##."),
+    ('P', "###.#..##..####.#...#..."),
+    ('R', "###.#..##..####.#.#.#..#"),
+    ('S', ".####...#....##....####."),
+    ('U', "#..##..##..##..##..#.##."),
+    ('Z', "####...#..#..#..#...####"),
+];
+
+#[allow(unused)]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct OcrError {
+    // What could be read, `?` for the unrecognized glyphs
+    pub partial: String,
+    // Index of each unrecognized glyph, from the left
+    pub unrecognized: Vec<usize>,
+}
+
+impl Display for OcrError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "could only read {:?}, unrecognized glyphs at", self.partial)?;
+        for (i, glyph) in self.unrecognized.iter().enumerate() {
+            let col = glyph * GLYPH_STRIDE;
+            write!(f, "{} {glyph} (columns {col}-{})", if i == 0 { "" } else { "," }, col + GLYPH_WIDTH - 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for OcrError {}
+
+// Reads the letters drawn with the 4x6 font on a screen of `cols` columns
+// and 6 rows, lit pixels being `#`
+#[allow(unused)]
+pub fn read_letters(pixels: &[char], cols: usize) -> Result<String, OcrError> {
+    assert_eq!(cols * GLYPH_HEIGHT, pixels.len(), "the screen must be {GLYPH_HEIGHT} rows high");
+    let glyphs = cols.div_ceil(GLYPH_STRIDE);
+    let mut partial = String::with_capacity(glyphs);
+    let mut unrecognized = vec![];
+    let mut glyph = String::with_capacity(GLYPH_WIDTH * GLYPH_HEIGHT);
+
+    for idx in 0..glyphs {
+        glyph.clear();
+        for row in 0..GLYPH_HEIGHT {
+            for col in (idx * GLYPH_STRIDE)..(idx * GLYPH_STRIDE + GLYPH_WIDTH) {
+                let lit = col < cols && pixels[row * cols + col] == '#';
+                glyph.push(if lit { '#' } else { '.' });
+            }
+        }
+        match FONT.iter().find(|(_, pattern)| *pattern == glyph) {
+            Some((letter, _)) => partial.push(*letter),
+            None => {
+                partial.push('?');
+                unrecognized.push(idx);
+            }
+        }
+    }
+
+    if unrecognized.is_empty() {
+        Ok(partial)
+    } else {
+        Err(OcrError { partial, unrecognized })
+    }
+}
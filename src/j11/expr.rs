@@ -0,0 +1,203 @@
+use std::fmt::{Display, Formatter};
+use std::iter::Peekable;
+use std::str::{CharIndices, FromStr};
+
+use crate::j11::worry::Worry;
+
+#[allow(unused)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Expr {
+    Old,
+    Num(u64),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+}
+
+#[allow(unused)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ExprError {
+    UnexpectedChar { position: usize, c: char },
+    UnexpectedEnd,
+    InvalidNumber(String),
+}
+
+impl Display for ExprError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExprError::UnexpectedChar { position, c } => write!(f, "unexpected {c:?} at position {position}"),
+            ExprError::UnexpectedEnd => write!(f, "unexpected end of expression"),
+            ExprError::InvalidNumber(number) => write!(f, "invalid number {number}"),
+        }
+    }
+}
+
+impl std::error::Error for ExprError {}
+
+// Why an exact evaluation failed
+#[allow(unused)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum EvalError {
+    Overflow,
+    Negative,
+}
+
+// Precedence climbing over `old`, numbers, `+`, `-`, `*` and parentheses
+struct Parser<'a> {
+    s: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_spaces(&mut self) {
+        while self.chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+    }
+
+    fn peek(&mut self) -> Option<(usize, char)> {
+        self.skip_spaces();
+        self.chars.peek().copied()
+    }
+
+    fn expr(&mut self) -> Result<Expr, ExprError> {
+        let mut lhs = self.term()?;
+        while let Some((_, c @ ('+' | '-'))) = self.peek() {
+            self.chars.next();
+            let rhs = self.term()?;
+            lhs = match c {
+                '+' => Expr::Add(Box::new(lhs), Box::new(rhs)),
+                _ => Expr::Sub(Box::new(lhs), Box::new(rhs)),
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn term(&mut self) -> Result<Expr, ExprError> {
+        let mut lhs = self.atom()?;
+        while let Some((_, '*')) = self.peek() {
+            self.chars.next();
+            let rhs = self.atom()?;
+            lhs = Expr::Mul(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn atom(&mut self) -> Result<Expr, ExprError> {
+        match self.peek() {
+            None => Err(ExprError::UnexpectedEnd),
+            Some((_, '(')) => {
+                self.chars.next();
+                let inner = self.expr()?;
+                match self.peek() {
+                    Some((_, ')')) => {
+                        self.chars.next();
+                        Ok(inner)
+                    }
+                    Some((position, c)) => Err(ExprError::UnexpectedChar { position, c }),
+                    None => Err(ExprError::UnexpectedEnd),
+                }
+            }
+            Some((start, c)) if c.is_ascii_digit() => {
+                let mut end = start;
+                while let Some((position, c)) = self.chars.next_if(|(_, c)| c.is_ascii_digit()) {
+                    end = position + c.len_utf8();
+                }
+                let number = &self.s[start..end];
+                u64::from_str(number).map(Expr::Num).map_err(|_| ExprError::InvalidNumber(number.to_string()))
+            }
+            Some((start, _)) if self.s[start..].starts_with("old") => {
+                for _ in 0..3 {
+                    self.chars.next();
+                }
+                match self.chars.peek() {
+                    Some((position, c)) if c.is_alphanumeric() => Err(ExprError::UnexpectedChar { position: *position, c: *c }),
+                    _ => Ok(Expr::Old),
+                }
+            }
+            Some((position, c)) => Err(ExprError::UnexpectedChar { position, c }),
+        }
+    }
+}
+
+impl FromStr for Expr {
+    type Err = ExprError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser { s, chars: s.char_indices().peekable() };
+        let expr = parser.expr()?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some((position, c)) => Err(ExprError::UnexpectedChar { position, c }),
+        }
+    }
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expr::Old => write!(f, "old"),
+            Expr::Num(num) => write!(f, "{num}"),
+            Expr::Add(lhs, rhs) => write!(f, "({lhs} + {rhs})"),
+            Expr::Sub(lhs, rhs) => write!(f, "({lhs} - {rhs})"),
+            Expr::Mul(lhs, rhs) => write!(f, "({lhs} * {rhs})"),
+        }
+    }
+}
+
+#[allow(unused)]
+impl Expr {
+    // Fails when a step does not fit or would be negative
+    pub fn eval<W: Worry>(&self, old: W) -> Result<W, EvalError> {
+        match self {
+            Expr::Old => Ok(old),
+            Expr::Num(num) => Ok(W::from_u64(*num)),
+            Expr::Add(lhs, rhs) => lhs.eval(old)?.checked_add(rhs.eval(old)?).ok_or(EvalError::Overflow),
+            Expr::Sub(lhs, rhs) => lhs.eval(old)?.checked_sub(rhs.eval(old)?).ok_or(EvalError::Negative),
+            Expr::Mul(lhs, rhs) => lhs.eval(old)?.checked_mul(rhs.eval(old)?).ok_or(EvalError::Overflow),
+        }
+    }
+
+    // `eval` modulo `modulus` when it succeeds. Never fails : a negative
+    // result wraps around the modulus, like in modular arithmetic
+    pub fn eval_mod<W: Worry>(&self, old: W, modulus: W) -> W {
+        match self {
+            Expr::Old => old % modulus,
+            Expr::Num(num) => W::from_u64(*num) % modulus,
+            Expr::Add(lhs, rhs) => lhs.eval_mod(old, modulus).add_mod(rhs.eval_mod(old, modulus), modulus),
+            Expr::Sub(lhs, rhs) => lhs.eval_mod(old, modulus).sub_mod(rhs.eval_mod(old, modulus), modulus),
+            Expr::Mul(lhs, rhs) => lhs.eval_mod(old, modulus).mul_mod(rhs.eval_mod(old, modulus), modulus),
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(unused)]
+mod expr_tests {
+    #[allow(unused)]
+    use super::*;
+
+    #[test]
+    #[allow(unused)]
+    fn test_parse_and_eval() {
+        let expr = Expr::from_str("old * 3 + 2").unwrap();
+        assert_eq!("((old * 3) + 2)", expr.to_string());
+        assert_eq!(Ok(32), expr.eval(10u64));
+
+        let expr = Expr::from_str("(old + old) * (old - 1)").unwrap();
+        assert_eq!(Ok(180), expr.eval(10u64));
+        assert_eq!(Err(EvalError::Negative), expr.eval(0u64));
+        assert_eq!(180 % 7, expr.eval_mod(10u64, 7));
+        // 0 * -1 = 0 either way, 1 - 3 wraps
+        assert_eq!(0, expr.eval_mod(0u64, 7));
+        assert_eq!(5, Expr::from_str("old - 3").unwrap().eval_mod(1u64, 7));
+        assert_eq!(Ok(10 - 3 - 2), Expr::from_str("old - 3 - 2").unwrap().eval(10u64));
+        assert_eq!(Err(EvalError::Overflow), Expr::from_str("old * old").unwrap().eval(u64::MAX));
+        assert_eq!(((u64::MAX as u128 * u64::MAX as u128) % 1_000_003) as u64, Expr::from_str("old * old").unwrap().eval_mod(u64::MAX, 1_000_003));
+        assert_eq!(Ok(u64::MAX as u128 * u64::MAX as u128), Expr::from_str("old * old").unwrap().eval(u64::MAX as u128));
+
+        assert_eq!(Err(ExprError::UnexpectedChar { position: 4, c: '/' }), Expr::from_str("old / 2"));
+        assert_eq!(Err(ExprError::UnexpectedEnd), Expr::from_str("old *"));
+        assert_eq!(Err(ExprError::UnexpectedEnd), Expr::from_str("(old + 1"));
+        assert_eq!(Err(ExprError::UnexpectedChar { position: 3, c: 'e' }), Expr::from_str("older"));
+        assert_eq!(Err(ExprError::UnexpectedChar { position: 4, c: '2' }), Expr::from_str("old 2"));
+    }
+}
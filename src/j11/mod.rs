@@ -1,30 +1,47 @@
+use std::fmt::{Display, Formatter, Write};
 use std::str::FromStr;
 
 use smallvec::{smallvec, SmallVec};
 
-#[derive(Clone)]
-enum Operation {
-    Plus(u64),
-    Times(u64),
-    Squared(),
+use crate::j11::expr::{EvalError, Expr, ExprError};
+use crate::j11::worry::{gcd, Worry};
+
+pub mod expr;
+pub mod worry;
+
+#[allow(unused)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Test {
+    DivisibleBy(u64),
+    Equals(u64),
+}
+
+impl Test {
+    #[inline(always)]
+    fn check<W: Worry>(&self, worry: W) -> bool {
+        match *self {
+            Test::DivisibleBy(divisor) => worry % W::from_u64(divisor) == W::ZERO,
+            Test::Equals(value) => worry == W::from_u64(value),
+        }
+    }
 }
 
 #[derive(Clone)]
-struct Monkey {
-    items: SmallVec<[u64; 30]>,
-    operation: Operation,
-    test: u64,
+pub struct Monkey<W: Worry = u64> {
+    items: SmallVec<[W; 30]>,
+    operation: Expr,
+    test: Test,
     send_true: usize,
     send_false: usize,
     inspections: usize,
 }
 
-impl Default for Monkey {
+impl<W: Worry> Default for Monkey<W> {
     fn default() -> Self {
         Monkey {
             items: smallvec![],
-            operation: Operation::Plus(0),
-            test: 0,
+            operation: Expr::Old,
+            test: Test::DivisibleBy(1),
             send_true: 0,
             send_false: 0,
             inspections: 0,
@@ -32,81 +49,270 @@ impl Default for Monkey {
     }
 }
 
-fn monkey_business(s: &str, divide_by_tree: bool, rounds: usize) -> usize {
-    let mut lines = s.lines();
-    let mut monkeys: SmallVec<[Monkey; 8]> = smallvec![];
-    loop {
+// `line` is 1 based
+#[allow(unused)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MonkeyError {
+    UnexpectedLine { line: usize, expected: &'static str },
+    InvalidNumber { line: usize, number: String },
+    InvalidOperation { line: usize, error: ExprError },
+    UnsupportedTest { line: usize, test: String },
+    UnknownMonkey { line: usize, monkey: usize },
+    Overflow { round: usize, monkey: usize, worry: u128 },
+    Negative { round: usize, monkey: usize, worry: u128 },
+}
+
+impl Display for MonkeyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MonkeyError::UnexpectedLine { line, expected } => write!(f, "line {line}: expected {expected}"),
+            MonkeyError::InvalidNumber { line, number } => write!(f, "line {line}: invalid number {number}"),
+            MonkeyError::InvalidOperation { line, error } => write!(f, "line {line}: invalid operation, {error}"),
+            MonkeyError::UnsupportedTest { line, test } => write!(f, "line {line}: unsupported test {test}"),
+            MonkeyError::UnknownMonkey { line, monkey } => write!(f, "line {line}: there is no monkey {monkey}"),
+            MonkeyError::Overflow { round, monkey, worry } =>
+                write!(f, "round {round}: monkey {monkey} overflowed the worry level of item {worry}"),
+            MonkeyError::Negative { round, monkey, worry } =>
+                write!(f, "round {round}: monkey {monkey} made the worry level of item {worry} negative"),
+        }
+    }
+}
+
+impl std::error::Error for MonkeyError {}
+
+fn field<'a>(lines: &mut impl Iterator<Item=(usize, &'a str)>, prefix: &str, expected: &'static str) -> Result<(usize, &'a str), MonkeyError> {
+    let (line_idx, line) = lines.next().ok_or(MonkeyError::UnexpectedLine { line: 0, expected })?;
+    let line_number = line_idx + 1;
+    line.trim_start().strip_prefix(prefix)
+        .map(|rest| (line_number, rest.trim()))
+        .ok_or(MonkeyError::UnexpectedLine { line: line_number, expected })
+}
+
+fn number<T: FromStr>(line: usize, number: &str) -> Result<T, MonkeyError> {
+    T::from_str(number).map_err(|_| MonkeyError::InvalidNumber { line, number: number.to_string() })
+}
+
+#[allow(unused)]
+pub fn parse_monkeys<W: Worry>(s: &str) -> Result<SmallVec<[Monkey<W>; 8]>, MonkeyError> {
+    let mut lines = s.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+    let mut monkeys: SmallVec<[Monkey<W>; 8]> = smallvec![];
+    let mut targets = vec![];
+    while let Some((line_idx, header)) = lines.next() {
+        if !header.starts_with("Monkey ") {
+            return Err(MonkeyError::UnexpectedLine { line: line_idx + 1, expected: "Monkey <n>:" });
+        }
         let mut monkey = Monkey::default();
-        lines.next().unwrap();
-        for number in lines.next().unwrap().split(": ").nth(1).unwrap().split(", ").map(|word| u64::from_str(word).unwrap()) {
-            monkey.items.push(number);
-        };
-        let mut raw_operation = lines.next().unwrap().split("old ").nth(1).unwrap().split(' ');
-        monkey.operation = match (raw_operation.next().unwrap(), u64::from_str(raw_operation.next().unwrap())) {
-            ("*", Ok(num)) => Operation::Times(num),
-            ("+", Ok(num)) => Operation::Plus(num),
-            ("*", _) => Operation::Squared(),
-            _ => panic!()
+
+        let (line, items) = field(&mut lines, "Starting items:", "Starting items: <items>")?;
+        for item in items.split(',').map(|item| item.trim()).filter(|item| !item.is_empty()) {
+            monkey.items.push(W::from_u64(number(line, item)?));
+        }
+
+        let (line, operation) = field(&mut lines, "Operation: new =", "Operation: new = <expression>")?;
+        monkey.operation = Expr::from_str(operation).map_err(|error| MonkeyError::InvalidOperation { line, error })?;
+
+        let (line, test) = field(&mut lines, "Test:", "Test: <test>")?;
+        monkey.test = if let Some(divisor) = test.strip_prefix("divisible by ") {
+            match number(line, divisor)? {
+                0 => return Err(MonkeyError::UnsupportedTest { line, test: test.to_string() }),
+                divisor => Test::DivisibleBy(divisor),
+            }
+        } else if let Some(value) = test.strip_prefix("equals ").or_else(|| test.strip_prefix("equal to ")) {
+            Test::Equals(number(line, value)?)
+        } else {
+            return Err(MonkeyError::UnsupportedTest { line, test: test.to_string() });
         };
-        monkey.test = u64::from_str(lines.next().unwrap().split("by ").nth(1).unwrap()).unwrap();
-        monkey.send_true = usize::from_str(lines.next().unwrap().split("monkey ").nth(1).unwrap()).unwrap();
-        monkey.send_false = usize::from_str(lines.next().unwrap().split("monkey ").nth(1).unwrap()).unwrap();
+
+        let (line, send_true) = field(&mut lines, "If true: throw to monkey", "If true: throw to monkey <n>")?;
+        monkey.send_true = number(line, send_true)?;
+        targets.push((line, monkey.send_true));
+        let (line, send_false) = field(&mut lines, "If false: throw to monkey", "If false: throw to monkey <n>")?;
+        monkey.send_false = number(line, send_false)?;
+        targets.push((line, monkey.send_false));
 
         monkeys.push(monkey);
-        match lines.next() {
-            None => { break; }
-            Some(_) => {}
+    }
+
+    match targets.into_iter().find(|(_, target)| *target >= monkeys.len()) {
+        Some((line, monkey)) => Err(MonkeyError::UnknownMonkey { line, monkey }),
+        None => Ok(monkeys),
+    }
+}
+
+// Keeping worries modulo the lcm of the divisors keeps every divisibility
+// test and every `+`, `-`, `*` operation correct, an `equals` test needs
+// the real value though
+#[allow(unused)]
+pub fn reduction_modulus<W: Worry>(monkeys: &[Monkey<W>]) -> Option<W> {
+    monkeys.iter().try_fold(W::ONE, |lcm, monkey| match monkey.test {
+        Test::DivisibleBy(divisor) => {
+            let divisor = W::from_u64(divisor);
+            lcm.checked_mul(divisor / gcd(lcm, divisor))
         }
+        Test::Equals(_) => None,
+    })
+}
+
+// Items held by every monkey and how many items each one inspected so far
+#[allow(unused)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MonkeyState<W: Worry = u64> {
+    pub round: usize,
+    pub items: Vec<Vec<W>>,
+    pub inspections: Vec<usize>,
+}
+
+#[allow(unused)]
+impl<W: Worry> MonkeyState<W> {
+    // (monkey, inspections) of the `n` busiest monkeys, busiest first
+    pub fn top(&self, n: usize) -> Vec<(usize, usize)> {
+        top_monkeys(&self.inspections, n)
+    }
+}
+
+// Same format as the puzzle statement
+impl<W: Worry> Display for MonkeyState<W> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (monkey, items) in self.items.iter().enumerate() {
+            write!(f, "Monkey {monkey}: ")?;
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{item}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+fn top_monkeys(inspections: &[usize], n: usize) -> Vec<(usize, usize)> {
+    let mut top = inspections.iter().copied().enumerate().collect::<Vec<_>>();
+    top.sort_by(|(monkey_a, a), (monkey_b, b)| b.cmp(a).then(monkey_a.cmp(monkey_b)));
+    top.truncate(n);
+    top
+}
+
+// The monkeys playing keep away, worry levels are stored as `W`, use u128
+// when the u64 levels or the product of the divisors overflow
+#[allow(unused)]
+pub struct Troop<W: Worry = u64> {
+    monkeys: SmallVec<[Monkey<W>; 8]>,
+    divide_by_tree: bool,
+    modulus: Option<W>,
+    round: usize,
+}
+
+#[allow(unused)]
+impl<W: Worry> Troop<W> {
+    pub fn parse(s: &str, divide_by_tree: bool) -> Result<Self, MonkeyError> {
+        let monkeys = parse_monkeys(s)?;
+        let modulus = if divide_by_tree { None } else { reduction_modulus(&monkeys) };
+        Ok(Self { monkeys, divide_by_tree, modulus, round: 0 })
+    }
+
+    // Rounds played so far
+    pub fn round(&self) -> usize {
+        self.round
     }
 
-    let monkey_mod: u64 = monkeys.iter().map(|m| m.test).product();
+    // None when worry levels are kept exact, and may overflow
+    pub fn modulus(&self) -> Option<W> {
+        self.modulus
+    }
 
-    for _ in 1..=rounds {
+    // Worry levels kept exact must stay positive and fit in `W`, with a
+    // modulus they wrap around it instead. The round is played on a copy of
+    // the monkeys, on failure the troop is left as it was before the round
+    pub fn play_round(&mut self) -> Result<(), MonkeyError> {
+        let round = self.round + 1;
+        let mut monkeys = self.monkeys.clone();
         for monkey_idx in 0..monkeys.len() {
-            let mut monkey = std::mem::take(&mut monkeys[monkey_idx]);
-            for item_idx in monkey.items.iter() {
-                monkey.inspections += 1;
-                let mut worry = *item_idx;
-                worry = match &monkey.operation {
-                    Operation::Plus(num) => {
-                        worry + num
-                    }
-                    Operation::Times(num) => {
-                        (worry) * (num)
-                    }
-                    Operation::Squared() => {
-                        worry * worry
-                    }
+            let items = std::mem::take(&mut monkeys[monkey_idx].items);
+            for item in items {
+                let monkey = &monkeys[monkey_idx];
+                let worry = match self.modulus {
+                    Some(modulus) => Ok(monkey.operation.eval_mod(item, modulus)),
+                    None => monkey.operation.eval(item),
                 };
+                let (monkey, item) = (monkey_idx, item.to_u128());
+                let mut worry = worry.map_err(|error| match error {
+                    EvalError::Overflow => MonkeyError::Overflow { round, monkey, worry: item },
+                    EvalError::Negative => MonkeyError::Negative { round, monkey, worry: item },
+                })?;
+                monkeys[monkey_idx].inspections += 1;
 
-                if divide_by_tree {
-                    worry /= 3;
-                } else if worry > monkey_mod {
-                    worry %= monkey_mod;
+                if self.divide_by_tree {
+                    worry = worry / W::from_u64(3);
                 }
 
-                if worry % monkey.test == 0 {
-                    monkeys[monkey.send_true].items.push(worry);
-                } else {
-                    monkeys[monkey.send_false].items.push(worry);
-                }
+                let monkey = &monkeys[monkey_idx];
+                let target = if monkey.test.check(worry) { monkey.send_true } else { monkey.send_false };
+                monkeys[target].items.push(worry);
             }
-            monkey.items.clear();
-            let _ = std::mem::replace(&mut monkeys[monkey_idx], monkey);
         }
+        self.monkeys = monkeys;
+        self.round = round;
+        Ok(())
+    }
+
+    pub fn play(&mut self, rounds: usize) -> Result<(), MonkeyError> {
+        for _ in 0..rounds {
+            self.play_round()?;
+        }
+        Ok(())
     }
 
-    let (first, second) = monkeys.into_iter().map(|monkey| monkey.inspections).fold((0, 0),
-                                                                                    |(first, second), elt| {
-                                                                                        if elt < second {
-                                                                                            (first, second)
-                                                                                        } else if elt < first {
-                                                                                            (first, elt)
-                                                                                        } else {
-                                                                                            (elt, first)
-                                                                                        }
-                                                                                    });
-    first * second
+    // Same as `play` with a snapshot after every round
+    pub fn play_with_snapshots(&mut self, rounds: usize) -> Result<Vec<MonkeyState<W>>, MonkeyError> {
+        let mut snapshots = Vec::with_capacity(rounds);
+        for _ in 0..rounds {
+            self.play_round()?;
+            snapshots.push(self.state());
+        }
+        Ok(snapshots)
+    }
+
+    pub fn state(&self) -> MonkeyState<W> {
+        MonkeyState {
+            round: self.round,
+            items: self.monkeys.iter().map(|monkey| monkey.items.to_vec()).collect(),
+            inspections: self.inspections(),
+        }
+    }
+
+    pub fn inspections(&self) -> Vec<usize> {
+        self.monkeys.iter().map(|monkey| monkey.inspections).collect()
+    }
+
+    pub fn top(&self, n: usize) -> Vec<(usize, usize)> {
+        top_monkeys(&self.inspections(), n)
+    }
+
+    // Product of the inspections of the two busiest monkeys
+    pub fn monkey_business(&self) -> usize {
+        self.top(2).iter().map(|(_, inspections)| inspections).product()
+    }
+
+    pub fn report(&self, n: usize) -> String {
+        let mut report = String::new();
+        for (rank, (monkey, inspections)) in self.top(n).into_iter().enumerate() {
+            writeln!(report, "{}. Monkey {monkey} inspected items {inspections} times.", rank + 1).unwrap();
+        }
+        report
+    }
+}
+
+#[allow(unused)]
+pub fn try_monkey_business<W: Worry>(s: &str, divide_by_tree: bool, rounds: usize) -> Result<usize, MonkeyError> {
+    let mut troop = Troop::<W>::parse(s, divide_by_tree)?;
+    troop.play(rounds)?;
+    Ok(troop.monkey_business())
+}
+
+fn monkey_business(s: &str, divide_by_tree: bool, rounds: usize) -> usize {
+    try_monkey_business::<u64>(s, divide_by_tree, rounds).unwrap()
 }
 
 #[allow(unused)]
@@ -148,4 +354,129 @@ mod j11_tests {
         assert_eq!(2713310158, _p2(include_str!("j11_test.txt")));
         assert_eq!(25712998901, _p2(include_str!("j11.txt")));
     }
-}
\ No newline at end of file
+
+    const CUSTOM_MONKEYS: &str = "Monkey 0:
+  Starting items: 3, 4
+  Operation: new = (old + old) * 3 + 2
+  Test: divisible by 4
+    If true: throw to monkey 1
+    If false: throw to monkey 1
+
+Monkey 1:
+  Starting items:
+  Operation: new = old * old - 1
+  Test: equals 7
+    If true: throw to monkey 0
+    If false: throw to monkey 0
+";
+
+    #[test]
+    #[allow(unused)]
+    fn test_custom_operations_and_tests() {
+        let monkeys = parse_monkeys::<u64>(CUSTOM_MONKEYS).unwrap();
+        assert_eq!(Test::Equals(7), monkeys[1].test);
+        assert_eq!(None, reduction_modulus(&monkeys));
+        assert_eq!(Some(96577), reduction_modulus(&parse_monkeys::<u64>(include_str!("j11_test.txt")).unwrap()));
+
+        // 3 -> 20 / 3 = 6 -> 35 / 3 = 11, 4 -> 26 / 3 = 8 -> 63 / 3 = 21
+        assert_eq!(4, try_monkey_business::<u64>(CUSTOM_MONKEYS, true, 1).unwrap());
+        assert_eq!(Err(MonkeyError::Overflow { round: 4, monkey: 1, worry: 7118703509349980 }), try_monkey_business::<u64>(CUSTOM_MONKEYS, false, 10).map(|_| ()));
+        let subtracting = CUSTOM_MONKEYS.replace("old * old - 1", "old - 100");
+        assert_eq!(Err(MonkeyError::Negative { round: 1, monkey: 1, worry: 6 }), try_monkey_business::<u64>(&subtracting, true, 1).map(|_| ()));
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_troop_after_overflow() {
+        let mut troop = Troop::<u64>::parse(CUSTOM_MONKEYS, false).unwrap();
+        troop.play(3).unwrap();
+        let state = troop.state();
+        let report = troop.report(2);
+        let error = troop.play(10).unwrap_err();
+        assert_eq!(MonkeyError::Overflow { round: 4, monkey: 1, worry: 7118703509349980 }, error);
+
+        // Monkey 0 had thrown its items before monkey 1 overflowed, the round is undone
+        assert_eq!(state, troop.state());
+        assert_eq!(3, troop.round());
+        assert_eq!(report, troop.report(2));
+
+        // Trying again fails the same way
+        assert_eq!(Err(error), troop.play_round());
+        assert_eq!(state, troop.state());
+
+        // Items a monkey threw to itself before overflowing are not lost
+        let mut troop = Troop::<u64>::parse("Monkey 0:
+  Starting items: 2, 1000000000000
+  Operation: new = old * old
+  Test: divisible by 2
+    If true: throw to monkey 0
+    If false: throw to monkey 0
+", true).unwrap();
+        assert_eq!(Err(MonkeyError::Overflow { round: 1, monkey: 0, worry: 1000000000000 }), troop.play_round());
+        assert_eq!(vec![vec![2, 1000000000000]], troop.state().items);
+        assert_eq!(vec![0], troop.inspections());
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_parse_errors() {
+        let errors = [
+            (CUSTOM_MONKEYS.replace("(old + old)", "old / 2"),
+             MonkeyError::InvalidOperation { line: 3, error: ExprError::UnexpectedChar { position: 4, c: '/' } }),
+            (CUSTOM_MONKEYS.replace("equals 7", "greater than 7"),
+             MonkeyError::UnsupportedTest { line: 11, test: "greater than 7".to_string() }),
+            (CUSTOM_MONKEYS.replace("false: throw to monkey 1", "false: throw to monkey 5"),
+             MonkeyError::UnknownMonkey { line: 6, monkey: 5 }),
+            (CUSTOM_MONKEYS.replace("3, 4", "3, x"),
+             MonkeyError::InvalidNumber { line: 2, number: "x".to_string() }),
+            (CUSTOM_MONKEYS.replace("  Operation: new = old * old - 1\n", ""),
+             MonkeyError::UnexpectedLine { line: 10, expected: "Operation: new = <expression>" }),
+        ];
+        for (input, error) in errors {
+            assert_eq!(Err(error), parse_monkeys::<u64>(&input).map(|_| ()));
+        }
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_snapshots_and_report() {
+        let mut troop = Troop::<u64>::parse(include_str!("j11_test.txt"), true).unwrap();
+        let snapshots = troop.play_with_snapshots(20).unwrap();
+        assert_eq!(20, snapshots.len());
+        assert_eq!("Monkey 0: 20, 23, 27, 26\nMonkey 1: 2080, 25, 167, 207, 401, 1046\nMonkey 2: \nMonkey 3: \n", snapshots[0].to_string());
+        assert_eq!(vec![101, 95, 7, 105], snapshots[19].inspections);
+        assert_eq!(vec![(3, 105), (0, 101), (1, 95)], snapshots[19].top(3));
+        assert_eq!("1. Monkey 3 inspected items 105 times.\n2. Monkey 0 inspected items 101 times.\n", troop.report(2));
+        assert_eq!(10605, troop.monkey_business());
+
+        let mut troop = Troop::<u64>::parse(include_str!("j11_test.txt"), false).unwrap();
+        assert_eq!(vec![2, 4, 3, 6], troop.play_with_snapshots(1).unwrap()[0].inspections);
+        troop.play(999).unwrap();
+        assert_eq!(1000, troop.round());
+        assert_eq!(vec![5204, 4792, 199, 5192], troop.inspections());
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_wide_worry_levels() {
+        assert_eq!(2713310158, try_monkey_business::<u128>(include_str!("j11_test.txt"), false, 10000).unwrap());
+        assert_eq!(10605, try_monkey_business::<u128>(include_str!("j11_test.txt"), true, 20).unwrap());
+
+        // 2^40 * 3^30 does not fit in a u64
+        let huge_divisors = include_str!("j11_test.txt")
+            .replacen("divisible by 23", "divisible by 1099511627776", 1)
+            .replacen("divisible by 19", "divisible by 205891132094649", 1);
+        assert_eq!(None, Troop::<u64>::parse(&huge_divisors, false).unwrap().modulus());
+        assert_eq!(Some(1099511627776 * 205891132094649 * 13 * 17), Troop::<u128>::parse(&huge_divisors, false).unwrap().modulus());
+        assert!(matches!(try_monkey_business::<u64>(&huge_divisors, false, 1000), Err(MonkeyError::Overflow { .. })));
+        assert!(try_monkey_business::<u128>(&huge_divisors, false, 1000).is_ok());
+
+        // Exact u128 levels last longer than u64 ones
+        let overflow_round = |result: Result<usize, MonkeyError>| match result {
+            Err(MonkeyError::Overflow { round, .. }) => round,
+            _ => panic!(),
+        };
+        assert!(overflow_round(try_monkey_business::<u64>(CUSTOM_MONKEYS, false, 10))
+            < overflow_round(try_monkey_business::<u128>(CUSTOM_MONKEYS, false, 10)));
+    }
+}
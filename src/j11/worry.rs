@@ -0,0 +1,154 @@
+use std::fmt::{Debug, Display};
+use std::ops::{Div, Rem};
+
+// Integer type holding worry levels, every operation which could overflow
+// is either checked or done modulo a reduction modulus
+pub trait Worry: Copy + Debug + Display + Default + Eq + Ord + Div<Output=Self> + Rem<Output=Self> {
+    const ZERO: Self;
+    const ONE: Self;
+
+    fn from_u64(value: u64) -> Self;
+    fn to_u128(self) -> u128;
+    fn checked_add(self, rhs: Self) -> Option<Self>;
+    fn checked_sub(self, rhs: Self) -> Option<Self>;
+    fn checked_mul(self, rhs: Self) -> Option<Self>;
+
+    // `self` and `rhs` are already reduced modulo `modulus`
+    fn add_mod(self, rhs: Self, modulus: Self) -> Self;
+    fn sub_mod(self, rhs: Self, modulus: Self) -> Self;
+    fn mul_mod(self, rhs: Self, modulus: Self) -> Self;
+}
+
+impl Worry for u64 {
+    const ZERO: Self = 0;
+    const ONE: Self = 1;
+
+    #[inline(always)]
+    fn from_u64(value: u64) -> Self {
+        value
+    }
+
+    #[inline(always)]
+    fn to_u128(self) -> u128 {
+        self as u128
+    }
+
+    #[inline(always)]
+    fn checked_add(self, rhs: Self) -> Option<Self> {
+        u64::checked_add(self, rhs)
+    }
+
+    #[inline(always)]
+    fn checked_sub(self, rhs: Self) -> Option<Self> {
+        u64::checked_sub(self, rhs)
+    }
+
+    #[inline(always)]
+    fn checked_mul(self, rhs: Self) -> Option<Self> {
+        u64::checked_mul(self, rhs)
+    }
+
+    #[inline(always)]
+    fn add_mod(self, rhs: Self, modulus: Self) -> Self {
+        ((self as u128 + rhs as u128) % modulus as u128) as u64
+    }
+
+    #[inline(always)]
+    fn sub_mod(self, rhs: Self, modulus: Self) -> Self {
+        ((self as u128 + modulus as u128 - rhs as u128) % modulus as u128) as u64
+    }
+
+    #[inline(always)]
+    fn mul_mod(self, rhs: Self, modulus: Self) -> Self {
+        ((self as u128 * rhs as u128) % modulus as u128) as u64
+    }
+}
+
+impl Worry for u128 {
+    const ZERO: Self = 0;
+    const ONE: Self = 1;
+
+    #[inline(always)]
+    fn from_u64(value: u64) -> Self {
+        value as u128
+    }
+
+    #[inline(always)]
+    fn to_u128(self) -> u128 {
+        self
+    }
+
+    #[inline(always)]
+    fn checked_add(self, rhs: Self) -> Option<Self> {
+        u128::checked_add(self, rhs)
+    }
+
+    #[inline(always)]
+    fn checked_sub(self, rhs: Self) -> Option<Self> {
+        u128::checked_sub(self, rhs)
+    }
+
+    #[inline(always)]
+    fn checked_mul(self, rhs: Self) -> Option<Self> {
+        u128::checked_mul(self, rhs)
+    }
+
+    #[inline(always)]
+    fn add_mod(self, rhs: Self, modulus: Self) -> Self {
+        // self + rhs >= modulus without computing the sum
+        if self >= modulus - rhs { self - (modulus - rhs) } else { self + rhs }
+    }
+
+    #[inline(always)]
+    fn sub_mod(self, rhs: Self, modulus: Self) -> Self {
+        if self >= rhs { self - rhs } else { modulus - (rhs - self) }
+    }
+
+    fn mul_mod(self, rhs: Self, modulus: Self) -> Self {
+        if modulus <= u64::MAX as u128 {
+            return (self * rhs) % modulus;
+        }
+        // Double and add, the product does not fit
+        let mut result = 0;
+        let mut base = self;
+        let mut exp = rhs;
+        while exp > 0 {
+            if exp & 1 == 1 {
+                result = result.add_mod(base, modulus);
+            }
+            base = base.add_mod(base, modulus);
+            exp >>= 1;
+        }
+        result
+    }
+}
+
+#[inline(always)]
+pub fn gcd<W: Worry>(a: W, b: W) -> W {
+    if b == W::ZERO { a } else { gcd(b, a % b) }
+}
+
+#[cfg(test)]
+#[allow(unused)]
+mod worry_tests {
+    #[allow(unused)]
+    use super::*;
+
+    #[test]
+    #[allow(unused)]
+    fn test_modular_operations() {
+        assert_eq!(((u64::MAX as u128 * (u64::MAX - 1) as u128) % 1_000_003) as u64,
+                   (u64::MAX % 1_000_003).mul_mod((u64::MAX - 1) % 1_000_003, 1_000_003));
+        assert_eq!(2, 3u64.sub_mod(8, 7));
+
+        let modulus = u128::MAX - 158;
+        let a = modulus - 1;
+        let b = modulus - 2;
+        // (-1) * (-2) = 2
+        assert_eq!(2, a.mul_mod(b, modulus));
+        // (-1) + (-2) = -3
+        assert_eq!(modulus - 3, a.add_mod(b, modulus));
+        assert_eq!(1, b.sub_mod(a, modulus).add_mod(2, modulus));
+        assert_eq!(6, gcd(48u128, 18));
+    }
+}
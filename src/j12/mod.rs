@@ -1,201 +1,108 @@
-use std::cmp::Ordering;
+use crate::grid::{Grid, Point};
+use crate::search::bfs;
 
-use smallvec::{SmallVec, smallvec};
-
-#[derive(Debug, Clone)]
-struct Link {
-    pub node: usize,
-    pub weight: i32,
-}
-
-#[derive(Debug, Clone)]
-struct NodeToExplore {
-    pub node: usize,
-    pub tag: i32,
-}
-
-#[derive(Debug, Clone)]
-struct NodeNeighbours {
-    pub neighbours: SmallVec<[Link; 4]>,
-}
-
-trait Comparer {
-    type T;
-    fn compare(&self, elt1: &Self::T, elt2: &Self::T) -> Ordering;
-}
-
-struct NodeToExploreComparer {
-    target_row: usize,
-    target_col: usize,
-    cols: usize,
+#[allow(unused)]
+pub struct Heightmap {
+    pub heights: Grid<u8>,
+    pub start: Point,
+    pub end: Point,
 }
 
-impl NodeToExploreComparer {
-    fn estimate(&self, elt: &NodeToExplore) -> i32 {
-        ((elt.node / self.cols).abs_diff(self.target_row) +
-            (elt.node % self.cols).abs_diff(self.target_col)) as i32
+#[allow(unused)]
+impl Heightmap {
+    pub fn parse(s: &str) -> Self {
+        let mut start = (0, 0);
+        let mut end = (0, 0);
+        let letters = Grid::parse(s, Some).unwrap();
+        let heights = Grid::from_fn(letters.rows(), letters.cols(), |point| match letters[point] {
+            'S' => {
+                start = point;
+                0
+            }
+            'E' => {
+                end = point;
+                b'z' - b'a'
+            }
+            c => c as u8 - b'a',
+        });
+        Self { heights, start, end }
     }
-}
-
-impl Comparer for NodeToExploreComparer {
-    type T = NodeToExplore;
 
-    fn compare(&self, elt1: &NodeToExplore, elt2: &NodeToExplore) -> Ordering {
-        (elt1.tag + self.estimate(elt1)).cmp(&(elt2.tag + self.estimate(elt2)))
+    // One can climb at most one unit, and go down any height
+    pub fn climbable(&self, point: Point) -> impl Iterator<Item=Point> + '_ {
+        let height = self.heights[point];
+        self.heights.neighbors4(point).filter(move |neighbor| self.heights[*neighbor] <= height + 1)
     }
-}
 
-pub fn compute_path_cost<const EXPECTED_NODES_COUNT: usize,
-    const EXPECTED_NODES_TAG: usize>(s: &str, any_square: bool, use_heuristic: bool) -> usize {
-    let rows = s.lines().enumerate().count();
-    let cols = s.lines().next().unwrap().chars().count();
-
-    let mut nodes_height: SmallVec<[i32; EXPECTED_NODES_COUNT]> = smallvec![];
-
-    let mut start_node = 0;
-    let mut end_node = 0;
-
-    for (row, line) in s.lines().enumerate() {
-        for (col, char) in line.chars().enumerate() {
-            nodes_height.push(match char {
-                'S' => {
-                    start_node = row * cols + col;
-                    0i32
-                }
-                'E' => {
-                    end_node = row * cols + col;
-                    (b'z' - b'a') as i32
-                }
-                c => (c as u8 - b'a') as i32,
-            });
-        }
+    // Squares from which `point` is climbable
+    pub fn descendable(&self, point: Point) -> impl Iterator<Item=Point> + '_ {
+        let height = self.heights[point];
+        self.heights.neighbors4(point).filter(move |neighbor| self.heights[*neighbor] + 1 >= height)
     }
+}
 
-    let mut nodes_neighbours: SmallVec<[NodeNeighbours; EXPECTED_NODES_COUNT]> = smallvec![];
-
-    for node in 0..nodes_height.len() {
-        let mut node_neighbours = NodeNeighbours {
-            neighbours: smallvec![]
-        };
-        let row = node / cols;
-        let col = node % cols;
-        let height = nodes_height[node];
-
-        if col > 0 {
-            let target_node = node - 1;
-            if nodes_height[target_node] - height <= 1 {
-                node_neighbours.neighbours.push(Link {
-                    node: target_node,
-                    weight: 1,
-                })
-            }
-        }
-
-        if col < cols - 1 {
-            let target_node = node + 1;
-            if nodes_height[target_node] - height <= 1 {
-                node_neighbours.neighbours.push(Link {
-                    node: target_node,
-                    weight: 1,
-                })
-            }
-        }
-
-        if row > 0 {
-            let target_node = node - cols;
-            if nodes_height[target_node] - height <= 1 {
-                node_neighbours.neighbours.push(Link {
-                    node: target_node,
-                    weight: 1,
-                })
-            }
-        }
-
-        if row < rows - 1 {
-            let target_node = node + cols;
-            if nodes_height[target_node] - height <= 1 {
-                node_neighbours.neighbours.push(Link {
-                    node: target_node,
-                    weight: 1,
-                })
-            }
-        }
+// Squares visited in order, both ends included
+#[allow(unused)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Route {
+    pub points: Vec<Point>,
+}
 
-        nodes_neighbours.push(node_neighbours);
+#[allow(unused)]
+impl Route {
+    pub fn steps(&self) -> usize {
+        self.points.len() - 1
     }
 
-    // Dijkstra
-
-    let mut nodes_explored: SmallVec<[bool; EXPECTED_NODES_COUNT]> = smallvec![];
-    let mut nodes_tags: SmallVec<[NodeToExplore; EXPECTED_NODES_TAG]> = smallvec![];
-
-    for _ in 0..nodes_height.len() {
-        nodes_explored.push(false);
+    pub fn start(&self) -> Point {
+        self.points[0]
     }
 
-    if any_square {
-        for (idx, height) in nodes_height.iter().enumerate() {
-            if *height == 0 {
-                nodes_tags.push(NodeToExplore {
-                    node: idx,
-                    tag: 0,
-                })
-            }
+    // Same drawing as the puzzle statement, every square of the route shows
+    // where it goes next
+    pub fn render(&self, map: &Heightmap) -> String {
+        let mut drawing = map.heights.map(|_| '.');
+        for pair in self.points.windows(2) {
+            let ((row, col), (next_row, next_col)) = (pair[0], pair[1]);
+            drawing[pair[0]] = if next_row < row {
+                '^'
+            } else if next_row > row {
+                'v'
+            } else if next_col < col {
+                '<'
+            } else {
+                '>'
+            };
         }
-    } else {
-        nodes_tags.push(NodeToExplore {
-            node: start_node,
-            tag: 0,
-        });
+        drawing[map.end] = 'E';
+        drawing.render(|c| *c)
     }
+}
 
-    let cmp = NodeToExploreComparer {
-        target_row: end_node / rows,
-        target_col: end_node % rows,
-        cols,
-    };
-
-    while !nodes_tags.is_empty() {
-        let current_node = if use_heuristic {
-            (0..nodes_tags.len())
-                .min_by(|elt1, elt2|
-                    cmp.compare(&nodes_tags[*elt1], &nodes_tags[*elt2])).unwrap()
-        } else {
-            // We don't need to sort as the grid is uniform
-            0
-        };
-
-        if nodes_explored[nodes_tags[current_node].node] {
-            nodes_tags.remove(current_node);
-            continue;
-        }
-
-        if nodes_tags[current_node].node == end_node {
-            return nodes_tags[current_node].tag as usize;
-        }
-
-        for neighbour in nodes_neighbours[nodes_tags[current_node].node].neighbours.iter() {
-            if nodes_explored[neighbour.node] {
-                continue;
-            }
+#[allow(unused)]
+impl Heightmap {
+    // Shortest route from `S` to `E`
+    pub fn route(&self) -> Option<Route> {
+        bfs([self.start], |point| self.climbable(*point), |point| *point == self.end)
+            .path()
+            .map(|points| Route { points })
+    }
 
-            nodes_tags.push(NodeToExplore {
-                node: neighbour.node,
-                tag: nodes_tags[current_node].tag + neighbour.weight,
+    // Shortest route from any `a` square to `E`, found by walking down from `E`
+    pub fn best_route(&self) -> Option<Route> {
+        bfs([self.end], |point| self.descendable(*point), |point| self.heights[*point] == 0)
+            .path()
+            .map(|mut points| {
+                points.reverse();
+                Route { points }
             })
-        }
-
-        nodes_explored[nodes_tags[current_node].node] = true;
-        nodes_tags.remove(current_node);
     }
-
-    panic!("No Path Found, wtf ?")
 }
 
 
 #[allow(unused)]
 pub fn _p1(s: &str) -> usize {
-    compute_path_cost::<2501, 10004>(s, false, false)
+    Heightmap::parse(s).route().expect("No Path Found, wtf ?").steps()
 }
 
 #[allow(unused)]
@@ -205,7 +112,7 @@ pub fn p1() -> usize {
 
 #[allow(unused)]
 pub fn _p2(s: &str) -> usize {
-    compute_path_cost::<2501, 10004>(s, true, true)
+    Heightmap::parse(s).best_route().expect("No Path Found, wtf ?").steps()
 }
 
 #[allow(unused)]
@@ -232,4 +139,20 @@ mod j12_tests {
         assert_eq!(29, _p2(include_str!("j12_test.txt")));
         assert_eq!(321, _p2(include_str!("j12.txt")));
     }
-}
\ No newline at end of file
+
+    #[test]
+    #[allow(unused)]
+    fn test_routes() {
+        let map = Heightmap::parse(include_str!("j12_test.txt"));
+        let route = map.route().unwrap();
+        assert_eq!((0, 0), route.start());
+        assert_eq!(map.end, *route.points.last().unwrap());
+        assert_eq!(31, route.steps());
+        assert_eq!(">>vv<<<<\n..vvv<<^\n..vv>E^^\n..v>>>^^\n..>>>>>^\n", route.render(&map));
+
+        let best = map.best_route().unwrap();
+        assert_eq!(29, best.steps());
+        assert_eq!((4, 0), best.start());
+        assert_eq!(0, map.heights[best.start()]);
+    }
+}
@@ -1,6 +1,13 @@
 use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
 use std::iter::Peekable;
-use std::str::Chars;
+use std::str::{Chars, FromStr};
+
+use smallvec::{smallvec, SmallVec};
+
+use crate::j13::packet::{Packet, PacketError};
+
+pub mod packet;
 
 #[inline(always)]
 fn is_digit(c: &char) -> bool {
@@ -12,42 +19,50 @@ fn are_digits(c1: &char, c2: &char) -> bool {
     is_digit(c1) && is_digit(c2)
 }
 
+// Digits of the number, leading zeros excluded
 #[inline(always)]
-fn get_first_digit(chars: &mut Peekable<Chars>) -> u8 {
-    let mut num = 0;
+fn get_first_digits(chars: &mut Peekable<Chars>) -> SmallVec<[u8; 20]> {
+    let mut digits = smallvec![];
     while let Some(c) = chars.peek() {
         if !is_digit(c) {
             break;
         }
-        num = 10 * num + (*c as u8 - b'0');
+        if !digits.is_empty() || *c != '0' {
+            digits.push(*c as u8 - b'0');
+        }
         chars.next().unwrap();
     }
 
-    num
+    digits
 }
 
+// A longer number is a bigger one
 #[inline(always)]
 fn compare_digits(first_chars: &mut Peekable<Chars>, second_chars: &mut Peekable<Chars>) -> Ordering {
-    get_first_digit(first_chars).cmp(&get_first_digit(second_chars))
+    let first = get_first_digits(first_chars);
+    let second = get_first_digits(second_chars);
+    first.len().cmp(&second.len()).then_with(|| first.cmp(&second))
+}
+
+// Compares two packets without building them, both must be well formed
+// (see `Packet::from_str`)
+#[allow(unused)]
+pub fn compare_str(first: &str, second: &str) -> Ordering {
+    compare_streaming(&mut first.chars().peekable(), &mut second.chars().peekable())
 }
 
-fn parse(first_chars: &mut Peekable<Chars>, second_chars: &mut Peekable<Chars>) -> Ordering {
+fn compare_streaming(first_chars: &mut Peekable<Chars>, second_chars: &mut Peekable<Chars>) -> Ordering {
     let mut first_left_to_match = 0;
     let mut second_left_to_match = 0;
     loop {
         match (first_chars.peek(), second_chars.peek()) {
-            (None, None) => { panic!() }
+            (None, None) => return Ordering::Equal,
             (Some(_), None) => return Ordering::Greater,
             (None, Some(_)) => return Ordering::Less,
             (Some(v1), Some(v2)) => {
                 match (v1, v2) {
                     (',', c) if !is_digit(c) && *c != ']' && second_left_to_match > first_left_to_match => return Ordering::Greater,
                     (c, ',') if !is_digit(c) && *c != ']' && first_left_to_match > second_left_to_match => return Ordering::Less,
-                    (v1, v2) if v1 == v2 => {
-                        first_chars.next().unwrap();
-                        second_chars.next().unwrap();
-                        continue;
-                    }
                     (']', _) if second_left_to_match > first_left_to_match => {
                         first_chars.next().unwrap();
                         second_left_to_match -= 1;
@@ -61,6 +76,11 @@ fn parse(first_chars: &mut Peekable<Chars>, second_chars: &mut Peekable<Chars>)
                         Ordering::Equal => continue,
                         Ordering::Greater => return Ordering::Greater,
                     },
+                    (v1, v2) if v1 == v2 => {
+                        first_chars.next().unwrap();
+                        second_chars.next().unwrap();
+                        continue;
+                    }
                     ('[', ']') => return Ordering::Greater,
                     (']', '[') => return Ordering::Less,
                     (',', ']') => return Ordering::Greater,
@@ -77,10 +97,7 @@ fn parse(first_chars: &mut Peekable<Chars>, second_chars: &mut Peekable<Chars>)
                     }
                     (',', c) if is_digit(c) => return Ordering::Less,
                     (c, ',') if is_digit(c) => return Ordering::Greater,
-                    (a, b) => {
-                        dbg!(a, b);
-                        panic!()
-                    }
+                    (a, b) => unreachable!("malformed packets, {a:?} against {b:?}"),
                 }
             }
         }
@@ -88,6 +105,99 @@ fn parse(first_chars: &mut Peekable<Chars>, second_chars: &mut Peekable<Chars>)
 }
 
 
+// `line` is 1 based
+#[allow(unused)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum InputError {
+    InvalidPacket { line: usize, error: PacketError },
+    UnpairedPacket { line: usize },
+}
+
+impl Display for InputError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InputError::InvalidPacket { line, error } => write!(f, "line {line}: {error}"),
+            InputError::UnpairedPacket { line } => write!(f, "line {line}: packet without a pair"),
+        }
+    }
+}
+
+impl std::error::Error for InputError {}
+
+// Every packet of the input with its line, blank lines are skipped
+#[allow(unused)]
+pub fn parse_packets(s: &str) -> Result<Vec<(usize, Packet)>, InputError> {
+    s.lines().enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(line_idx, line)| Packet::from_str(line.trim())
+            .map(|packet| (line_idx + 1, packet))
+            .map_err(|error| InputError::InvalidPacket { line: line_idx + 1, error }))
+        .collect()
+}
+
+#[allow(unused)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PacketPair {
+    // 1 based, as in the puzzle
+    pub index: usize,
+    pub left: Packet,
+    pub right: Packet,
+}
+
+#[allow(unused)]
+pub fn parse_pairs(s: &str) -> Result<Vec<PacketPair>, InputError> {
+    let packets = parse_packets(s)?;
+    if packets.len() % 2 == 1 {
+        return Err(InputError::UnpairedPacket { line: packets[packets.len() - 1].0 });
+    }
+    let mut pairs = vec![];
+    let mut packets = packets.into_iter().map(|(_, packet)| packet);
+    while let (Some(left), Some(right)) = (packets.next(), packets.next()) {
+        pairs.push(PacketPair { index: pairs.len() + 1, left, right });
+    }
+    Ok(pairs)
+}
+
+// Pairs of the input which are not in the right order
+#[allow(unused)]
+pub fn misordered_pairs(s: &str) -> Result<Vec<PacketPair>, InputError> {
+    Ok(parse_pairs(s)?.into_iter().filter(|pair| pair.left >= pair.right).collect())
+}
+
+#[allow(unused)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SortedPackets {
+    pub packets: Vec<Packet>,
+    // 1 based position of every divider, in the order they were given
+    pub divider_positions: Vec<usize>,
+}
+
+#[allow(unused)]
+impl SortedPackets {
+    pub fn decoder_key(&self) -> usize {
+        self.divider_positions.iter().product()
+    }
+}
+
+// Sorts the packets of the input along with `dividers`, a divider comes
+// after the packets equal to it
+#[allow(unused)]
+pub fn sort_with_dividers(s: &str, dividers: &[Packet]) -> Result<SortedPackets, InputError> {
+    let mut tagged = parse_packets(s)?.into_iter()
+        .map(|(_, packet)| (packet, None))
+        .chain(dividers.iter().cloned().enumerate().map(|(idx, divider)| (divider, Some(idx))))
+        .collect::<Vec<_>>();
+    tagged.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut divider_positions = vec![0; dividers.len()];
+    for (position, (_, divider)) in tagged.iter().enumerate() {
+        if let Some(idx) = divider {
+            divider_positions[*idx] = position + 1;
+        }
+    }
+    Ok(SortedPackets { packets: tagged.into_iter().map(|(packet, _)| packet).collect(), divider_positions })
+}
+
 #[allow(unused)]
 pub fn _p1(s: &str) -> usize {
     let mut lines = s.lines();
@@ -102,7 +212,7 @@ pub fn _p1(s: &str) -> usize {
         let mut first = first.chars().peekable();
         let mut second = second.chars().peekable();
 
-        let ordering = parse(&mut first, &mut second);
+        let ordering = compare_streaming(&mut first, &mut second);
         match ordering {
             Ordering::Less => { total += pair_index }
             Ordering::Equal => {}
@@ -140,7 +250,7 @@ pub fn _p2(s: &str) -> usize {
         let mut first = first_str.chars().peekable();
         let mut two = two_str.chars().peekable();
 
-        match parse(&mut first, &mut two) {
+        match compare_streaming(&mut first, &mut two) {
             Ordering::Less => { total_under_2 += 1 }
             Ordering::Equal => {}
             Ordering::Greater => {}
@@ -149,7 +259,7 @@ pub fn _p2(s: &str) -> usize {
         let mut first = first_str.chars().peekable();
         let mut six = six_str.chars().peekable();
 
-        match parse(&mut first, &mut six) {
+        match compare_streaming(&mut first, &mut six) {
             Ordering::Less => { total_under_6 += 1 }
             Ordering::Equal => {}
             Ordering::Greater => {}
@@ -158,7 +268,7 @@ pub fn _p2(s: &str) -> usize {
         let mut second = second_str.chars().peekable();
         let mut two = two_str.chars().peekable();
 
-        match parse(&mut second, &mut two) {
+        match compare_streaming(&mut second, &mut two) {
             Ordering::Less => { total_under_2 += 1 }
             Ordering::Equal => {}
             Ordering::Greater => {}
@@ -167,7 +277,7 @@ pub fn _p2(s: &str) -> usize {
         let mut second = second_str.chars().peekable();
         let mut six = six_str.chars().peekable();
 
-        match parse(&mut second, &mut six) {
+        match compare_streaming(&mut second, &mut six) {
             Ordering::Less => { total_under_6 += 1 }
             Ordering::Equal => {}
             Ordering::Greater => {}
@@ -193,6 +303,10 @@ mod j13_tests {
     #[allow(unused)]
     use super::*;
 
+    use rand::{Rng, SeedableRng};
+    use rand_xoshiro::Xoshiro256PlusPlus;
+
+
     #[test]
     #[allow(unused)]
     fn test_p1() {
@@ -206,4 +320,66 @@ mod j13_tests {
         assert_eq!(140, _p2(include_str!("j13_test.txt")));
         assert_eq!(25800, _p2(include_str!("j13.txt")));
     }
-}
\ No newline at end of file
+
+    fn random_packet(rng: &mut Xoshiro256PlusPlus, depth: usize) -> String {
+        if depth > 0 && rng.gen_bool(0.6) {
+            let len = rng.gen_range(0..4);
+            let items = (0..len).map(|_| random_packet(rng, depth - 1)).collect::<Vec<_>>();
+            format!("[{}]", items.join(","))
+        } else if rng.gen_bool(0.1) {
+            // Past u64, and sometimes past u8 only
+            format!("{}{}", rng.gen_range(1..4), "0".repeat(rng.gen_range(2..25)))
+        } else {
+            rng.gen_range(0..4).to_string()
+        }
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_streaming_comparator_agrees_with_packets() {
+        let mut pairs = vec![];
+        for input in [include_str!("j13_test.txt"), include_str!("j13.txt")] {
+            let lines = input.lines().filter(|line| !line.is_empty()).collect::<Vec<_>>();
+            for first in lines.iter() {
+                for second in lines.iter().take(40) {
+                    pairs.push((first.to_string(), second.to_string()));
+                }
+            }
+        }
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(13);
+        for _ in 0..20000 {
+            let first = format!("[{}]", random_packet(&mut rng, 4));
+            let second = format!("[{}]", random_packet(&mut rng, 4));
+            pairs.push((first, second));
+        }
+
+        for (first, second) in pairs {
+            let expected = Packet::from_str(&first).unwrap().cmp(&Packet::from_str(&second).unwrap());
+            assert_eq!(expected, compare_str(&first, &second), "{first} against {second}");
+            assert_eq!(expected.reverse(), compare_str(&second, &first), "{second} against {first}");
+        }
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_sort_and_misordered_pairs() {
+        let dividers = [Packet::divider(2), Packet::divider(6)];
+        let sorted = sort_with_dividers(include_str!("j13_test.txt"), &dividers).unwrap();
+        assert_eq!(vec![10, 14], sorted.divider_positions);
+        assert_eq!(140, sorted.decoder_key());
+        assert_eq!("[]", sorted.packets[0].to_string());
+        assert_eq!("[9]", sorted.packets[17].to_string());
+        assert!(sorted.packets.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(25800, sort_with_dividers(include_str!("j13.txt"), &dividers).unwrap().decoder_key());
+
+        let misordered = misordered_pairs(include_str!("j13_test.txt")).unwrap();
+        assert_eq!(vec![3, 5, 7, 8], misordered.iter().map(|pair| pair.index).collect::<Vec<_>>());
+        assert_eq!("[[[]]]", misordered[2].left.to_string());
+        let misordered = misordered_pairs(include_str!("j13.txt")).unwrap();
+        let pairs = parse_pairs(include_str!("j13.txt")).unwrap();
+        assert_eq!(6369, (1..=pairs.len()).sum::<usize>() - misordered.iter().map(|pair| pair.index).sum::<usize>());
+
+        assert_eq!(Err(InputError::UnpairedPacket { line: 4 }), parse_pairs("[1]\n[2]\n\n[3]\n"));
+        assert_eq!(Err(InputError::InvalidPacket { line: 2, error: PacketError::UnexpectedEnd }), parse_pairs("[1]\n[2\n"));
+    }
+}
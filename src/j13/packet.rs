@@ -0,0 +1,285 @@
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter, Write};
+use std::iter::Peekable;
+use std::str::{CharIndices, FromStr};
+
+// Non negative integer of any size, kept as its decimal digits without
+// leading zeros so that a longer number is always a bigger one
+#[allow(unused)]
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Integer(String);
+
+impl Integer {
+    fn from_digits(digits: &str) -> Self {
+        let trimmed = digits.trim_start_matches('0');
+        Integer(if trimmed.is_empty() { "0".to_string() } else { trimmed.to_string() })
+    }
+}
+
+impl From<u64> for Integer {
+    fn from(value: u64) -> Self {
+        Integer(value.to_string())
+    }
+}
+
+impl Ord for Integer {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.len().cmp(&other.0.len()).then_with(|| self.0.cmp(&other.0))
+    }
+}
+
+impl PartialOrd for Integer {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Display for Integer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[allow(unused)]
+#[derive(Clone, Debug)]
+pub enum Packet {
+    Int(Integer),
+    List(Vec<Packet>),
+}
+
+#[allow(unused)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PacketError {
+    UnexpectedChar { position: usize, c: char },
+    UnexpectedEnd,
+}
+
+impl Display for PacketError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PacketError::UnexpectedChar { position, c } => write!(f, "unexpected {c:?} at position {position}"),
+            PacketError::UnexpectedEnd => write!(f, "unexpected end of packet"),
+        }
+    }
+}
+
+impl std::error::Error for PacketError {}
+
+struct Parser<'a> {
+    s: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    fn packet(&mut self) -> Result<Packet, PacketError> {
+        match self.chars.peek().copied() {
+            None => Err(PacketError::UnexpectedEnd),
+            Some((_, '[')) => {
+                self.chars.next();
+                let mut list = vec![];
+                if self.chars.next_if(|(_, c)| *c == ']').is_some() {
+                    return Ok(Packet::List(list));
+                }
+                loop {
+                    list.push(self.packet()?);
+                    match self.chars.next() {
+                        Some((_, ',')) => {}
+                        Some((_, ']')) => return Ok(Packet::List(list)),
+                        Some((position, c)) => return Err(PacketError::UnexpectedChar { position, c }),
+                        None => return Err(PacketError::UnexpectedEnd),
+                    }
+                }
+            }
+            Some((start, c)) if c.is_ascii_digit() => {
+                let mut end = start;
+                while let Some((position, _)) = self.chars.next_if(|(_, c)| c.is_ascii_digit()) {
+                    end = position + 1;
+                }
+                Ok(Packet::Int(Integer::from_digits(&self.s[start..end])))
+            }
+            Some((position, c)) => Err(PacketError::UnexpectedChar { position, c }),
+        }
+    }
+}
+
+impl FromStr for Packet {
+    type Err = PacketError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser { s, chars: s.char_indices().peekable() };
+        let packet = parser.packet()?;
+        match parser.chars.next() {
+            None => Ok(packet),
+            Some((position, c)) => Err(PacketError::UnexpectedChar { position, c }),
+        }
+    }
+}
+
+impl Display for Packet {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Packet::Int(int) => write!(f, "{int}"),
+            Packet::List(list) => {
+                write!(f, "[")?;
+                for (i, packet) in list.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{packet}")?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+#[allow(unused)]
+impl Packet {
+    pub fn int(value: u64) -> Self {
+        Packet::Int(Integer::from(value))
+    }
+
+    // Same result as `cmp`, with every step written like the puzzle walkthrough
+    pub fn explain(&self, other: &Packet) -> (Ordering, String) {
+        let mut trace = String::new();
+        let ordering = explain_rec(self, other, 0, &mut trace);
+        (ordering, trace)
+    }
+
+    // `[[value]]`, the shape of the divider packets
+    pub fn divider(value: u64) -> Self {
+        Packet::List(vec![Packet::List(vec![Packet::int(value)])])
+    }
+}
+
+fn explain_rec(left: &Packet, right: &Packet, depth: usize, trace: &mut String) -> Ordering {
+    let indent = "  ".repeat(depth);
+    writeln!(trace, "{indent}- Compare {left} vs {right}").unwrap();
+    match (left, right) {
+        (Packet::Int(a), Packet::Int(b)) => {
+            let ordering = a.cmp(b);
+            match ordering {
+                Ordering::Less => writeln!(trace, "{indent}  - Left side is smaller, so inputs are in the right order").unwrap(),
+                Ordering::Greater => writeln!(trace, "{indent}  - Right side is smaller, so inputs are not in the right order").unwrap(),
+                Ordering::Equal => {}
+            }
+            ordering
+        }
+        (Packet::List(a), Packet::List(b)) => {
+            for (left, right) in a.iter().zip(b.iter()) {
+                let ordering = explain_rec(left, right, depth + 1, trace);
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            let ordering = a.len().cmp(&b.len());
+            match ordering {
+                Ordering::Less => writeln!(trace, "{indent}  - Left side ran out of items, so inputs are in the right order").unwrap(),
+                Ordering::Greater => writeln!(trace, "{indent}  - Right side ran out of items, so inputs are not in the right order").unwrap(),
+                Ordering::Equal => {}
+            }
+            ordering
+        }
+        (Packet::Int(_), Packet::List(_)) => {
+            let left = Packet::List(vec![left.clone()]);
+            writeln!(trace, "{indent}  - Mixed types; convert left to {left} and retry comparison").unwrap();
+            explain_rec(&left, right, depth + 1, trace)
+        }
+        (Packet::List(_), Packet::Int(_)) => {
+            let right = Packet::List(vec![right.clone()]);
+            writeln!(trace, "{indent}  - Mixed types; convert right to {right} and retry comparison").unwrap();
+            explain_rec(left, &right, depth + 1, trace)
+        }
+    }
+}
+
+// The puzzle rules : integers compare by value, lists element by element
+// and an integer against a list compares as a list holding only itself
+impl Ord for Packet {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Packet::Int(a), Packet::Int(b)) => a.cmp(b),
+            (Packet::List(a), Packet::List(b)) => a.cmp(b),
+            (Packet::Int(_), Packet::List(b)) => std::slice::from_ref(self).cmp(b.as_slice()),
+            (Packet::List(a), Packet::Int(_)) => a.as_slice().cmp(std::slice::from_ref(other)),
+        }
+    }
+}
+
+impl PartialOrd for Packet {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Consistent with `Ord`, so `[[1]]` equals `1`
+impl PartialEq for Packet {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Packet {}
+
+#[cfg(test)]
+#[allow(unused)]
+mod packet_tests {
+    #[allow(unused)]
+    use super::*;
+
+    #[test]
+    #[allow(unused)]
+    fn test_parse_and_display() {
+        let packet = Packet::from_str("[1,[2,[3,[4,[5,6,7]]]],8,9]").unwrap();
+        assert_eq!("[1,[2,[3,[4,[5,6,7]]]],8,9]", packet.to_string());
+        assert_eq!("[[],0,7]", Packet::from_str("[[],000,007]").unwrap().to_string());
+        assert_eq!("[[2]]", Packet::divider(2).to_string());
+
+        assert_eq!(Err(PacketError::UnexpectedChar { position: 2, c: ';' }), Packet::from_str("[1;2]"));
+        assert_eq!(Err(PacketError::UnexpectedEnd), Packet::from_str("[1,[2]"));
+        assert_eq!(Err(PacketError::UnexpectedChar { position: 3, c: ']' }), Packet::from_str("[1]]"));
+        assert_eq!(Err(PacketError::UnexpectedChar { position: 3, c: ']' }), Packet::from_str("[1,]"));
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_ordering() {
+        let packet = |s: &str| Packet::from_str(s).unwrap();
+        assert!(packet("[1,1,3,1,1]") < packet("[1,1,5,1,1]"));
+        assert!(packet("[[1],[2,3,4]]") < packet("[[1],4]"));
+        assert!(packet("[9]") > packet("[[8,7,6]]"));
+        assert!(packet("[[[]]]") > packet("[[]]"));
+        assert_eq!(packet("[[1]]"), packet("[1]"));
+        assert!(packet("[256]") > packet("[255]"));
+        assert!(packet("[123456789012345678901234567890]") > packet("[99999999999999999999999999999]"));
+        assert!(packet("[007]") < packet("[10]"));
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_explain() {
+        let packet = |s: &str| Packet::from_str(s).unwrap();
+        let (ordering, trace) = packet("[[1],[2,3,4]]").explain(&packet("[[1],4]"));
+        assert_eq!(Ordering::Less, ordering);
+        assert_eq!("- Compare [[1],[2,3,4]] vs [[1],4]
+  - Compare [1] vs [1]
+    - Compare 1 vs 1
+  - Compare [2,3,4] vs 4
+    - Mixed types; convert right to [4] and retry comparison
+    - Compare [2,3,4] vs [4]
+      - Compare 2 vs 4
+        - Left side is smaller, so inputs are in the right order
+", trace);
+
+        let (ordering, trace) = packet("[[[]]]").explain(&packet("[[]]"));
+        assert_eq!(Ordering::Greater, ordering);
+        assert_eq!("- Compare [[[]]] vs [[]]
+  - Compare [[]] vs []
+    - Right side ran out of items, so inputs are not in the right order
+", trace);
+
+        let (ordering, trace) = packet("[7,7,7]").explain(&packet("[7,7,7]"));
+        assert_eq!(Ordering::Equal, ordering);
+        assert_eq!(4, trace.lines().count());
+    }
+}
@@ -1,172 +1,10 @@
-use std::str::FromStr;
+use crate::j14::world::{SandWorld, StopCondition};
 
-use smallvec::{SmallVec, smallvec};
-
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
-enum CellType {
-    Empty,
-    Sand,
-    Rock,
-}
-
-impl Default for CellType {
-    #[inline(always)]
-    fn default() -> Self {
-        CellType::Empty
-    }
-}
-
-#[inline(always)]
-fn get_row_and_col_min_max(s: &str) -> (i32, i32, i32, i32) {
-    let mut row_min = 0;
-    let mut row_max = 0;
-    let mut col_min = i32::MAX;
-    let mut col_max = 500;
-
-    for line in s.lines() {
-        let coordinates_split = line.split(" -> ");
-        for coord_str in coordinates_split {
-            let mut split_x_y = coord_str.split(',');
-            let col = i32::from_str(split_x_y.next().unwrap()).unwrap();
-            let row = i32::from_str(split_x_y.next().unwrap()).unwrap();
-
-            row_min = row_min.min(row);
-            row_max = row_max.max(row);
-            col_min = col_min.min(col);
-            col_max = col_max.max(col);
-        }
-    }
-    (row_min, row_max, col_min, col_max)
-}
-
-#[inline(always)]
-fn fill_rocks<const EXPECTED_CELLS_LENGTH: usize>(s: &str, row_min: i32, col_min: i32, rows: i32, cols: i32) -> SmallVec<[CellType; EXPECTED_CELLS_LENGTH]> {
-    let mut cells: SmallVec<[CellType; EXPECTED_CELLS_LENGTH]> = smallvec![];
-
-    for _ in 0..(rows * cols) {
-        cells.push(CellType::Empty);
-    }
-
-    for line in s.lines() {
-        let mut last_coordinate = None; //(row, col)
-        let coordinates_split = line.split(" -> ");
-        for coord_str in coordinates_split {
-            let mut split_x_y = coord_str.split(',');
-            let target_col = i32::from_str(split_x_y.next().unwrap()).unwrap() - col_min;
-            let target_row = i32::from_str(split_x_y.next().unwrap()).unwrap() - row_min;
-
-            match last_coordinate {
-                None => {}
-                Some((from_row, from_col)) => {
-                    let row_range = if target_row >= from_row {
-                        from_row..=target_row
-                    } else {
-                        target_row..=from_row
-                    };
-
-                    let col_range = if target_col >= from_col {
-                        from_col..=target_col
-                    } else {
-                        target_col..=from_col
-                    };
-
-                    for row in row_range {
-                        for col in col_range.clone() {
-                            cells[(row * cols + col) as usize] = CellType::Rock;
-                        }
-                    }
-                }
-            }
-            last_coordinate = Some((target_row, target_col));
-        }
-    }
-    cells
-}
-
-#[inline(always)]
-#[allow(unused)]
-fn show_sand_world<const EXPECTED_CELLS_LENGTH: usize>(rows: i32, cols: i32, cells: &mut SmallVec<[CellType; EXPECTED_CELLS_LENGTH]>) {
-    dbg!((rows, cols));
-    for row in 0..rows {
-        for col in 0..cols {
-            print!("{}", match cells[(row * cols + col) as usize] {
-                CellType::Empty => '.',
-                CellType::Rock => '#',
-                CellType::Sand => 'o',
-            });
-        }
-        println!()
-    }
-    println!();
-}
-
-#[inline(always)]
-fn drop_sand_until_filled_or_fall_off<const EXPECTED_CELLS_LENGTH: usize>(row_min: i32, col_min: i32, rows: i32, cols: i32, cells: &mut SmallVec<[CellType; EXPECTED_CELLS_LENGTH]>) -> usize {
-    let mut rest_units = 0;
-    'outer: loop {
-        let mut sand_coord = (0 - row_min, 500 - col_min);
-
-        loop {
-            if sand_coord.0 >= rows - 1 {
-                break 'outer;
-            }
-
-            if let CellType::Empty = cells[((sand_coord.0 + 1) * cols + sand_coord.1) as usize] {
-                sand_coord.0 += 1;
-                continue;
-            }
-
-            if sand_coord.1 == 0 {
-                break 'outer;
-            }
-
-            if let CellType::Empty = cells[((sand_coord.0 + 1) * cols + sand_coord.1 - 1) as usize] {
-                sand_coord.0 += 1;
-                sand_coord.1 -= 1;
-                continue;
-            }
-
-            if sand_coord.1 == cols - 1 {
-                break 'outer;
-            }
-
-            if let CellType::Empty = cells[((sand_coord.0 + 1) * cols + sand_coord.1 + 1) as usize] {
-                sand_coord.0 += 1;
-                sand_coord.1 += 1;
-                continue;
-            }
-
-            rest_units += 1;
-            cells[(sand_coord.0 * cols + sand_coord.1) as usize] = CellType::Sand;
-
-            break;
-        }
-
-        if sand_coord.0 == 0 - row_min && sand_coord.1 == 500 - col_min {
-            break;
-        }
-    }
-
-    rest_units as usize
-}
-
-#[inline(always)]
-fn compute_rows_and_cols(row_min: i32, row_max: i32, col_min: i32, col_max: i32) -> (i32, i32) {
-    let rows = row_max - row_min + 1;
-    let cols = col_max - col_min + 1;
-    (rows, cols)
-}
+pub mod world;
 
 #[allow(unused)]
 pub fn _p1(s: &str) -> usize {
-    const EXPECTED_CELLS_LENGTH: usize = 11270;
-    let (mut row_min, mut row_max, mut col_min, mut col_max) = get_row_and_col_min_max(s);
-
-    let (rows, cols) = compute_rows_and_cols(row_min, row_max, col_min, col_max);
-
-    let mut cells = fill_rocks::<EXPECTED_CELLS_LENGTH>(s, row_min, col_min, rows, cols);
-
-    drop_sand_until_filled_or_fall_off::<EXPECTED_CELLS_LENGTH>(row_min, col_min, rows, cols, &mut cells)
+    SandWorld::parse(s).unwrap().run_memoized(StopCondition::FirstFallOff)
 }
 
 #[allow(unused)]
@@ -176,43 +14,7 @@ pub fn p1() -> usize {
 
 #[allow(unused)]
 pub fn _p2(s: &str) -> usize {
-    const EXPECTED_CELLS_LENGTH: usize = 53301;
-    let (mut row_min, mut row_max, mut col_min, mut col_max) = get_row_and_col_min_max(s);
-
-    // Increase world size
-    row_max += 2;
-    col_min = col_min.min(500 - row_max - 1);
-    col_max = col_max.max(500 + row_max + 1);
-
-    let (rows, cols) = compute_rows_and_cols(row_min, row_max, col_min, col_max);
-
-    let mut cells = fill_rocks::<EXPECTED_CELLS_LENGTH>(s, row_min, col_min, rows, cols);
-
-    // Fill bottom with rocks
-    for col in 0..cols {
-        cells[(row_max * cols + col) as usize] = CellType::Rock
-    }
-
-    // Fill Forced Empty spaces with rocks
-    for row in 1..(rows - 1) {
-        for col in 1..(cols - 1) {
-            if cells[((row - 1) * cols + col) as usize] == CellType::Rock &&
-                cells[((row - 1) * cols + col - 1) as usize] == CellType::Rock &&
-                cells[((row - 1) * cols + col + 1) as usize] == CellType::Rock {
-                cells[(row * cols + col) as usize] = CellType::Rock;
-            }
-        }
-    }
-
-    // Count rocks
-    let rocks_count = cells.iter()
-        .take(((rows - 1) * cols) as usize)
-        .filter(|cell| **cell == CellType::Rock)
-        .count();
-
-    // Empty spaces are full space minus rocks
-    let rows = (rows - 1) as usize;
-    rows * rows - rocks_count
+    SandWorld::parse(s).unwrap().with_floor(2).count_until_blocked().unwrap()
 }
 
 #[allow(unused)]
@@ -226,6 +28,8 @@ mod j14_tests {
     #[allow(unused)]
     use super::*;
 
+    use crate::j14::world::{CellType, DropOutcome, generate_cave, ParseWorldError};
+
     #[test]
     #[allow(unused)]
     fn test_p1() {
@@ -239,4 +43,79 @@ mod j14_tests {
         assert_eq!(93, _p2(include_str!("j14_test.txt")));
         assert_eq!(23925, _p2(include_str!("j14.txt")));
     }
-}
\ No newline at end of file
+
+    #[test]
+    #[allow(unused)]
+    fn test_sand_world() {
+        let mut world = SandWorld::parse(include_str!("j14_test.txt")).unwrap();
+        assert_eq!(9, world.lowest_rock());
+        assert_eq!(DropOutcome::Rested((500, 8)), world.drop_from((500, 0)));
+        world.run(StopCondition::FirstFallOff);
+        assert_eq!(24, world.count(CellType::Sand));
+        assert!(world.render().contains("..+.."));
+        assert!(world.render().contains("#########."));
+
+        // Without a floor every source ends up pouring into the abyss
+        let mut world = SandWorld::parse(include_str!("j14_test.txt")).unwrap();
+        assert_eq!(24, world.run(StopCondition::SourcesBlocked));
+
+        let mut world = SandWorld::parse(include_str!("j14_test.txt")).unwrap().with_floor(2);
+        assert_eq!(Some(11), world.floor());
+        assert_eq!(93, world.run(StopCondition::SourcesBlocked));
+        assert_eq!(DropOutcome::Blocked, world.drop_from((500, 0)));
+        let (x_min, x_max) = world.x_range();
+        assert!(x_min <= 490 && x_max >= 510);
+
+        // A source right above the bottom rocks, sand spills over their left end
+        let mut world = SandWorld::parse(include_str!("j14_test.txt")).unwrap().with_sources(&[(497, 7)]);
+        assert_eq!(4, world.run(StopCondition::FirstFallOff));
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_multiple_sources() {
+        // Two full triangles 12 rows high, apart then overlapping
+        let triangle: usize = (0..12).map(|y| 2 * y + 1).sum();
+        let mut world = SandWorld::parse("0,10 -> 1,10").unwrap().with_floor(2).with_sources(&[(100, 0), (200, 0)]);
+        assert_eq!(2 * triangle, world.run(StopCondition::SourcesBlocked));
+
+        let mut world = SandWorld::parse("0,10 -> 1,10").unwrap().with_floor(2).with_sources(&[(100, 0), (104, 0)]);
+        let union: usize = 2 + 6 + (2..12).map(|y| 2 * y + 5).sum::<usize>();
+        assert_eq!(union, world.run(StopCondition::SourcesBlocked));
+
+        // The first unit of the second source falls off
+        let mut world = SandWorld::parse(include_str!("j14_test.txt")).unwrap().with_sources(&[(500, 0), (520, 0)]);
+        assert_eq!(1, world.run(StopCondition::FirstFallOff));
+
+        assert_eq!(Err(ParseWorldError::DiagonalSegment { line: 1, from: (0, 0), to: (1, 1) }), SandWorld::parse("0,0 -> 1,1").map(|_| ()));
+        assert_eq!(Err(ParseWorldError::InvalidPoint { line: 2, point: " 3,x".to_string() }), SandWorld::parse("0,0 -> 1,0\n2,2 -> 3,x").map(|_| ()));
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_memoized_and_counted_match_the_simulation() {
+        let sources = [(500, 0), (480, 3), (530, 1)];
+        for (seed, input) in [(0, include_str!("j14.txt").to_string()), (1, generate_cave(1, 200, 80, 60)), (2, generate_cave(2, 400, 150, 150))] {
+            let world = SandWorld::parse(&input).unwrap();
+            for sources in [&sources[..1], &sources[..]] {
+                let world = world.clone().with_sources(sources);
+                let mut naive = world.clone();
+                let mut memoized = world.clone();
+                assert_eq!(naive.run(StopCondition::FirstFallOff), memoized.run_memoized(StopCondition::FirstFallOff), "seed {seed}");
+                assert_eq!(naive.render(), memoized.render());
+                // Resuming after some sand already rested
+                assert_eq!(naive.clone().with_floor(2).run(StopCondition::SourcesBlocked),
+                           memoized.clone().with_floor(2).count_until_blocked().unwrap());
+
+                let floored = world.with_floor(3);
+                let mut naive = floored.clone();
+                let mut memoized = floored.clone();
+                let expected = naive.run(StopCondition::SourcesBlocked);
+                assert_eq!(expected, memoized.run_memoized(StopCondition::SourcesBlocked), "seed {seed}");
+                assert_eq!(expected, floored.count_until_blocked().unwrap(), "seed {seed}");
+                assert_eq!(naive.render(), memoized.render());
+            }
+        }
+        assert_eq!(None, SandWorld::parse(include_str!("j14_test.txt")).unwrap().count_until_blocked());
+    }
+}
@@ -0,0 +1,381 @@
+use std::fmt::{Display, Formatter, Write};
+use std::str::FromStr;
+
+use rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoshiro256PlusPlus;
+
+use crate::grid::Grid;
+
+// (x, y) as in the puzzle input, y grows downwards
+pub type Position = (i32, i32);
+
+pub const DEFAULT_SOURCE: Position = (500, 0);
+
+#[allow(unused)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum CellType {
+    #[default]
+    Empty,
+    Sand,
+    Rock,
+}
+
+// `line` is 1 based
+#[allow(unused)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseWorldError {
+    InvalidPoint { line: usize, point: String },
+    DiagonalSegment { line: usize, from: Position, to: Position },
+}
+
+impl Display for ParseWorldError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseWorldError::InvalidPoint { line, point } => write!(f, "line {line}: invalid point {point}"),
+            ParseWorldError::DiagonalSegment { line, from, to } =>
+                write!(f, "line {line}: {},{} -> {},{} is neither horizontal nor vertical", from.0, from.1, to.0, to.1),
+        }
+    }
+}
+
+impl std::error::Error for ParseWorldError {}
+
+#[allow(unused)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DropOutcome {
+    Rested(Position),
+    // Went below the lowest rock, there is no floor to stop it
+    FellOff,
+    // The source is already covered by sand
+    Blocked,
+}
+
+#[allow(unused)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StopCondition {
+    // Every source is blocked, or pours into the abyss
+    SourcesBlocked,
+    FirstFallOff,
+}
+
+// Cave seen from the side, sand is poured from every source in turn and the
+// storage grows when sand piles up outside of it
+#[allow(unused)]
+#[derive(Clone, Debug)]
+pub struct SandWorld {
+    cells: Grid<CellType>,
+    // Position of the cell (0, 0) of `cells`
+    x_min: i32,
+    y_min: i32,
+    lowest_rock: i32,
+    floor: Option<i32>,
+    sources: Vec<Position>,
+}
+
+fn parse_point(line: usize, point: &str) -> Result<Position, ParseWorldError> {
+    let invalid = || ParseWorldError::InvalidPoint { line, point: point.to_string() };
+    let (x, y) = point.trim().split_once(',').ok_or_else(invalid)?;
+    Ok((i32::from_str(x).map_err(|_| invalid())?, i32::from_str(y).map_err(|_| invalid())?))
+}
+
+#[allow(unused)]
+impl SandWorld {
+    // Rock paths, one per line, with a single source at 500,0 and no floor
+    pub fn parse(s: &str) -> Result<Self, ParseWorldError> {
+        let mut segments = vec![];
+        for (line_idx, line) in s.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            let points = line.split("->")
+                .map(|point| parse_point(line_idx + 1, point))
+                .collect::<Result<Vec<_>, _>>()?;
+            for pair in points.windows(2) {
+                let (from, to) = (pair[0], pair[1]);
+                if from.0 != to.0 && from.1 != to.1 {
+                    return Err(ParseWorldError::DiagonalSegment { line: line_idx + 1, from, to });
+                }
+                segments.push((from, to));
+            }
+            if points.len() == 1 {
+                segments.push((points[0], points[0]));
+            }
+        }
+
+        let lowest_rock = segments.iter().map(|(from, to)| from.1.max(to.1)).max().unwrap_or(0);
+        let mut world = Self {
+            cells: Grid::new(0, 0, CellType::Empty),
+            x_min: DEFAULT_SOURCE.0,
+            y_min: DEFAULT_SOURCE.1,
+            lowest_rock,
+            floor: None,
+            sources: vec![DEFAULT_SOURCE],
+        };
+        for (from, to) in segments {
+            for x in from.0.min(to.0)..=from.0.max(to.0) {
+                for y in from.1.min(to.1)..=from.1.max(to.1) {
+                    world.set((x, y), CellType::Rock);
+                }
+            }
+        }
+        Ok(world)
+    }
+
+    pub fn with_sources(mut self, sources: &[Position]) -> Self {
+        self.sources = sources.to_vec();
+        self
+    }
+
+    // An infinite floor `depth` rows below the lowest rock
+    pub fn with_floor(mut self, depth: i32) -> Self {
+        self.floor = Some(self.lowest_rock + depth);
+        self
+    }
+
+    pub fn without_floor(mut self) -> Self {
+        self.floor = None;
+        self
+    }
+
+    pub fn sources(&self) -> &[Position] {
+        &self.sources
+    }
+
+    // Row of the floor, if any
+    pub fn floor(&self) -> Option<i32> {
+        self.floor
+    }
+
+    pub fn lowest_rock(&self) -> i32 {
+        self.lowest_rock
+    }
+
+    // Horizontal extent of the storage, which only grows
+    pub fn x_range(&self) -> (i32, i32) {
+        (self.x_min, self.x_min + self.cells.cols() as i32 - 1)
+    }
+
+    pub fn get(&self, (x, y): Position) -> CellType {
+        if Some(y) == self.floor {
+            return CellType::Rock;
+        }
+        if x < self.x_min || y < self.y_min {
+            return CellType::Empty;
+        }
+        self.cells.get(((y - self.y_min) as usize, (x - self.x_min) as usize)).copied().unwrap_or_default()
+    }
+
+    fn set(&mut self, (x, y): Position, cell: CellType) {
+        let (x_max, y_max) = (self.x_min + self.cells.cols() as i32 - 1, self.y_min + self.cells.rows() as i32 - 1);
+        if self.cells.is_empty() || x < self.x_min || x > x_max || y < self.y_min || y > y_max {
+            self.grow((x, y));
+        }
+        self.cells[((y - self.y_min) as usize, (x - self.x_min) as usize)] = cell;
+    }
+
+    // Reallocates so that `(x, y)` fits, with some margin on the side it
+    // grew from to amortize later growth
+    fn grow(&mut self, (x, y): Position) {
+        let margin = (self.cells.cols() as i32 / 2).max(8);
+        let (old_x_min, old_y_min) = (self.x_min, self.y_min);
+        let (old_x_max, old_y_max) = if self.cells.is_empty() {
+            (x, y)
+        } else {
+            (self.x_min + self.cells.cols() as i32 - 1, self.y_min + self.cells.rows() as i32 - 1)
+        };
+        let x_min = if x < old_x_min { x - margin } else { old_x_min.min(x) };
+        let x_max = if x > old_x_max { x + margin } else { old_x_max.max(x) };
+        let y_min = old_y_min.min(y);
+        let y_max = old_y_max.max(y).max(self.lowest_rock);
+
+        let old = std::mem::take(&mut self.cells);
+        self.cells = Grid::from_fn((y_max - y_min + 1) as usize, (x_max - x_min + 1) as usize, |(row, col)| {
+            let (x, y) = (x_min + col as i32, y_min + row as i32);
+            if x < old_x_min || y < old_y_min {
+                CellType::Empty
+            } else {
+                old.get(((y - old_y_min) as usize, (x - old_x_min) as usize)).copied().unwrap_or_default()
+            }
+        });
+        self.x_min = x_min;
+        self.y_min = y_min;
+    }
+
+    // Lets one unit of sand fall from `source` until it rests
+    pub fn drop_from(&mut self, source: Position) -> DropOutcome {
+        if self.get(source) != CellType::Empty {
+            return DropOutcome::Blocked;
+        }
+        let (mut x, mut y) = source;
+        loop {
+            if self.floor.is_none() && y > self.lowest_rock {
+                return DropOutcome::FellOff;
+            }
+            match [x, x - 1, x + 1].into_iter().find(|next_x| self.get((*next_x, y + 1)) == CellType::Empty) {
+                Some(next_x) => {
+                    x = next_x;
+                    y += 1;
+                }
+                None => {
+                    self.set((x, y), CellType::Sand);
+                    return DropOutcome::Rested((x, y));
+                }
+            }
+        }
+    }
+
+    // Same as `drop_from`, but resumes from where the previous unit of this
+    // source was still falling, `path` holds the positions it went through.
+    // Cells only ever fill up, so the path stays valid up to the first
+    // position which got covered since
+    pub fn drop_along(&mut self, path: &mut Vec<Position>) -> DropOutcome {
+        while path.last().is_some_and(|position| self.get(*position) != CellType::Empty) {
+            path.pop();
+        }
+        let Some(&(mut x, mut y)) = path.last() else {
+            return DropOutcome::Blocked;
+        };
+        loop {
+            if self.floor.is_none() && y > self.lowest_rock {
+                return DropOutcome::FellOff;
+            }
+            match [x, x - 1, x + 1].into_iter().find(|next_x| self.get((*next_x, y + 1)) == CellType::Empty) {
+                Some(next_x) => {
+                    x = next_x;
+                    y += 1;
+                    path.push((x, y));
+                }
+                None => {
+                    self.set((x, y), CellType::Sand);
+                    path.pop();
+                    return DropOutcome::Rested((x, y));
+                }
+            }
+        }
+    }
+
+    // Pours one unit from every active source in turn, returns how many
+    // units came to rest
+    pub fn run(&mut self, stop: StopCondition) -> usize {
+        self.run_with(stop, |world, source, _| world.drop_from(source))
+    }
+
+    // Same as `run`, every unit starts where the previous one of its source
+    // came to rest instead of at the source
+    pub fn run_memoized(&mut self, stop: StopCondition) -> usize {
+        let mut paths = self.sources.iter().map(|source| vec![*source]).collect::<Vec<_>>();
+        self.run_with(stop, |world, _, idx| world.drop_along(&mut paths[idx]))
+    }
+
+    fn run_with(&mut self, stop: StopCondition, mut drop: impl FnMut(&mut Self, Position, usize) -> DropOutcome) -> usize {
+        let mut active = (0..self.sources.len()).collect::<Vec<_>>();
+        let mut rested = 0;
+        while !active.is_empty() {
+            let mut idx = 0;
+            while idx < active.len() {
+                match drop(self, self.sources[active[idx]], active[idx]) {
+                    DropOutcome::Rested(_) => {
+                        rested += 1;
+                        idx += 1;
+                    }
+                    DropOutcome::FellOff if stop == StopCondition::FirstFallOff => return rested,
+                    // Once a unit fell off, every following one from this source does too
+                    DropOutcome::FellOff | DropOutcome::Blocked => {
+                        active.remove(idx);
+                    }
+                }
+            }
+        }
+        rested
+    }
+
+    // With a floor sand ends up on every cell reachable from a source by
+    // moving down, down left or down right without going through rock, so
+    // the result of `run(StopCondition::SourcesBlocked)` is counted row by
+    // row without simulating anything. Sand already at rest counts as
+    // reached. None without a floor
+    pub fn count_until_blocked(&self) -> Option<usize> {
+        let floor = self.floor?;
+        let y_start = self.sources.iter().map(|(_, y)| *y).min()?.min(self.y_min);
+        let spread = floor - y_start;
+        let x_start = self.sources.iter().map(|(x, _)| *x).min()?.min(self.x_min) - spread - 1;
+        let x_end = self.sources.iter().map(|(x, _)| *x).max()?.max(self.x_range().1) + spread + 1;
+        let width = (x_end - x_start + 1) as usize;
+
+        // Only the columns next to the ones reached on the previous row, or
+        // holding a source, can be reached
+        let mut previous = vec![false; width];
+        let mut current = vec![false; width];
+        let mut previous_range: Option<(usize, usize)> = None;
+        let mut reached = 0;
+        for y in y_start..floor {
+            let source_cols = self.sources.iter().filter(|(_, source_y)| *source_y == y).map(|(x, _)| (x - x_start) as usize);
+            let window = previous_range.map(|(lo, hi)| (lo.saturating_sub(1), (hi + 1).min(width - 1))).into_iter()
+                .chain(source_cols.map(|col| (col, col)))
+                .reduce(|(lo_a, hi_a), (lo_b, hi_b)| (lo_a.min(lo_b), hi_a.max(hi_b)));
+            let mut current_range: Option<(usize, usize)> = None;
+            if let Some((lo, hi)) = window {
+                for col in lo..=hi {
+                    let x = x_start + col as i32;
+                    let cell = self.get((x, y));
+                    current[col] = cell != CellType::Rock &&
+                        (cell == CellType::Sand || self.sources.contains(&(x, y)) ||
+                            previous[col] || (col > 0 && previous[col - 1]) || (col + 1 < width && previous[col + 1]));
+                    if current[col] {
+                        reached += 1;
+                        current_range = Some(current_range.map_or((col, col), |(lo, _)| (lo, col)));
+                    }
+                }
+            }
+            if let Some((lo, hi)) = previous_range {
+                previous[lo..=hi].fill(false);
+            }
+            std::mem::swap(&mut previous, &mut current);
+            previous_range = current_range;
+        }
+        Some(reached - self.count(CellType::Sand))
+    }
+
+    pub fn count(&self, cell: CellType) -> usize {
+        self.cells.iter().filter(|c| **c == cell).count()
+    }
+
+    // Same drawing as the puzzle statement, over the storage extent
+    pub fn render(&self) -> String {
+        let (x_min, x_max) = self.x_range();
+        let y_max = self.floor.unwrap_or(self.lowest_rock).max(self.y_min + self.cells.rows() as i32 - 1);
+        let mut drawing = String::new();
+        for y in self.y_min..=y_max {
+            for x in x_min..=x_max {
+                drawing.push(match self.get((x, y)) {
+                    CellType::Empty if self.sources.contains(&(x, y)) => '+',
+                    CellType::Empty => '.',
+                    CellType::Rock => '#',
+                    CellType::Sand => 'o',
+                });
+            }
+            drawing.push('\n');
+        }
+        drawing
+    }
+}
+
+// Random rock paths below 500,0 for benches and tests, `width` columns
+// centered on the source and `depth` rows
+#[allow(unused)]
+pub fn generate_cave(seed: u64, width: i32, depth: i32, paths: usize) -> String {
+    let mut rng = Xoshiro256PlusPlus::seed_from_u64(seed);
+    let mut cave = String::new();
+    for _ in 0..paths {
+        let mut x = DEFAULT_SOURCE.0 - width / 2 + rng.gen_range(0..width);
+        let mut y = rng.gen_range(2..depth);
+        write!(cave, "{x},{y}").unwrap();
+        for segment in 0..rng.gen_range(1..5) {
+            if segment % 2 == 0 {
+                x += rng.gen_range(-10..=10);
+            } else {
+                y = (y + rng.gen_range(-6..=6)).clamp(2, depth);
+            }
+            write!(cave, " -> {x},{y}").unwrap();
+        }
+        cave.push('\n');
+    }
+    cave
+}
@@ -0,0 +1,238 @@
+use std::collections::HashSet;
+use std::ops::RangeInclusive;
+
+// (x, y)
+pub type Point = (i64, i64);
+
+#[inline(always)]
+pub fn manhattan((x1, y1): Point, (x2, y2): Point) -> i64 {
+    (x1 - x2).abs() + (y1 - y2).abs()
+}
+
+// Points within `radius` of `center` in manhattan distance
+#[allow(unused)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Diamond {
+    pub center: Point,
+    pub radius: i64,
+}
+
+#[allow(unused)]
+impl Diamond {
+    pub fn new(center: Point, radius: i64) -> Self {
+        Self { center, radius }
+    }
+
+    // Area a sensor rules out, its beacon lies on the edge
+    pub fn from_sensor(sensor: Point, beacon: Point) -> Self {
+        Self::new(sensor, manhattan(sensor, beacon))
+    }
+
+    pub fn contains(&self, point: Point) -> bool {
+        manhattan(self.center, point) <= self.radius
+    }
+
+    // Inclusive columns covered on row `y`
+    pub fn row_span(&self, y: i64) -> Option<(i64, i64)> {
+        let half_width = self.radius - (self.center.1 - y).abs();
+        if half_width < 0 { None } else { Some((self.center.0 - half_width, self.center.0 + half_width)) }
+    }
+
+    pub fn area(&self) -> i64 {
+        2 * self.radius * (self.radius + 1) + 1
+    }
+
+    // In rotated coordinates u = x + y and v = x - y a diamond is a square,
+    // these are the u and v of the lines just outside of its edges
+    fn outer_lines(&self) -> ([i64; 2], [i64; 2]) {
+        let (u, v) = (self.center.0 + self.center.1, self.center.0 - self.center.1);
+        let reach = self.radius + 1;
+        ([u - reach, u + reach], [v - reach, v + reach])
+    }
+}
+
+// Sorts and merges inclusive intervals, touching ones included
+fn merge(mut intervals: Vec<(i64, i64)>) -> Vec<(i64, i64)> {
+    intervals.sort_unstable();
+    let mut merged: Vec<(i64, i64)> = Vec::with_capacity(intervals.len());
+    for (start, end) in intervals {
+        match merged.last_mut() {
+            Some((_, last_end)) if start <= *last_end + 1 => *last_end = (*last_end).max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+// Union of diamonds
+#[allow(unused)]
+#[derive(Clone, Debug, Default)]
+pub struct Coverage {
+    diamonds: Vec<Diamond>,
+}
+
+#[allow(unused)]
+impl Coverage {
+    pub fn new(diamonds: Vec<Diamond>) -> Self {
+        Self { diamonds }
+    }
+
+    pub fn diamonds(&self) -> &[Diamond] {
+        &self.diamonds
+    }
+
+    pub fn contains(&self, point: Point) -> bool {
+        self.diamonds.iter().any(|diamond| diamond.contains(point))
+    }
+
+    // Sorted disjoint inclusive intervals covered on row `y`
+    pub fn row_intervals(&self, y: i64) -> Vec<(i64, i64)> {
+        merge(self.diamonds.iter().filter_map(|diamond| diamond.row_span(y)).collect())
+    }
+
+    pub fn covered_in_row(&self, y: i64) -> i64 {
+        self.row_intervals(y).iter().map(|(start, end)| end - start + 1).sum()
+    }
+
+    // Covered points within the rectangle, one row at a time
+    pub fn covered_area(&self, xs: RangeInclusive<i64>, ys: RangeInclusive<i64>) -> i64 {
+        ys.map(|y| self.row_intervals(y).iter()
+            .map(|(start, end)| ((*end).min(*xs.end()) - (*start).max(*xs.start()) + 1).max(0))
+            .sum::<i64>())
+            .sum()
+    }
+
+    // Every uncovered point of the rectangle, at most `limit` of them,
+    // sorted. Each uncovered area has a corner where the lines just outside
+    // two diamonds, or such a line and the border, cross : those crossings
+    // are the seeds the areas are flood filled from. Diagonal neighbors are
+    // followed too, as a gap between two diamonds can be a diagonal streak
+    pub fn uncovered_points(&self, xs: RangeInclusive<i64>, ys: RangeInclusive<i64>, limit: usize) -> Vec<Point> {
+        let (mut us, mut vs) = (vec![], vec![]);
+        for diamond in self.diamonds.iter() {
+            let (u, v) = diamond.outer_lines();
+            us.extend(u);
+            vs.extend(v);
+        }
+        // The border, as u and v crossing the corners
+        let corners = [(*xs.start(), *ys.start()), (*xs.start(), *ys.end()), (*xs.end(), *ys.start()), (*xs.end(), *ys.end())];
+        for (x, y) in corners {
+            us.push(x + y);
+            vs.push(x - y);
+        }
+
+        let in_region = |(x, y): Point| xs.contains(&x) && ys.contains(&y);
+        let mut seeds = vec![];
+        for u in us.iter() {
+            for v in vs.iter() {
+                // Rounded down when u + v is odd, the point is then between 4 cells
+                let (x, y) = ((u + v).div_euclid(2), (u - v).div_euclid(2));
+                seeds.extend([(x, y), (x + 1, y), (x, y + 1), (x + 1, y + 1)]);
+            }
+            for x in [*xs.start(), *xs.end()] {
+                seeds.push((x, u - x));
+            }
+            for y in [*ys.start(), *ys.end()] {
+                seeds.push((u - y, y));
+            }
+        }
+        for v in vs.iter() {
+            for x in [*xs.start(), *xs.end()] {
+                seeds.push((x, x - v));
+            }
+            for y in [*ys.start(), *ys.end()] {
+                seeds.push((v + y, y));
+            }
+        }
+
+        let mut uncovered = HashSet::new();
+        let mut stack = vec![];
+        for seed in seeds {
+            if uncovered.len() >= limit {
+                break;
+            }
+            if !in_region(seed) || uncovered.contains(&seed) || self.contains(seed) {
+                continue;
+            }
+            uncovered.insert(seed);
+            stack.push(seed);
+            while let Some((x, y)) = stack.pop() {
+                for neighbor in [(x - 1, y - 1), (x, y - 1), (x + 1, y - 1), (x - 1, y), (x + 1, y), (x - 1, y + 1), (x, y + 1), (x + 1, y + 1)] {
+                    if uncovered.len() >= limit {
+                        break;
+                    }
+                    if in_region(neighbor) && !uncovered.contains(&neighbor) && !self.contains(neighbor) {
+                        uncovered.insert(neighbor);
+                        stack.push(neighbor);
+                    }
+                }
+            }
+        }
+        let mut uncovered = uncovered.into_iter().collect::<Vec<_>>();
+        uncovered.sort_unstable();
+        uncovered
+    }
+}
+
+#[cfg(test)]
+#[allow(unused)]
+mod diamond_tests {
+    #[allow(unused)]
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use rand_xoshiro::Xoshiro256PlusPlus;
+
+    #[test]
+    #[allow(unused)]
+    fn test_diamond() {
+        let diamond = Diamond::from_sensor((8, 7), (2, 10));
+        assert_eq!(9, diamond.radius);
+        assert!(diamond.contains((8, -2)));
+        assert!(!diamond.contains((9, -2)));
+        assert_eq!(Some((8, 8)), diamond.row_span(-2));
+        assert_eq!(Some((-1, 17)), diamond.row_span(7));
+        assert_eq!(None, diamond.row_span(17));
+        assert_eq!(181, diamond.area());
+        assert_eq!(diamond.area(), Coverage::new(vec![diamond]).covered_area(-20..=20, -20..=20));
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_coverage() {
+        let coverage = Coverage::new(vec![Diamond::new((0, 0), 2), Diamond::new((5, 0), 2), Diamond::new((20, 0), 1)]);
+        assert_eq!(vec![(-2, 7), (19, 21)], coverage.row_intervals(0));
+        assert_eq!(vec![(-1, 1), (4, 6), (20, 20)], coverage.row_intervals(1));
+        assert_eq!(13, coverage.covered_in_row(0));
+        assert_eq!(2 * 6 + 11, coverage.covered_area(0..=30, -1..=1));
+
+        // Against every point of the region, holes of any shape included
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(15);
+        for _ in 0..3000 {
+            let diamonds = (0..rng.gen_range(1..8))
+                .map(|_| Diamond::new((rng.gen_range(-5..25), rng.gen_range(-5..25)), rng.gen_range(0..9)))
+                .collect::<Vec<_>>();
+            let coverage = Coverage::new(diamonds);
+            let expected = (0..=20).flat_map(|x| (0..=20).map(move |y| (x, y))).filter(|point| !coverage.contains(*point)).collect::<Vec<_>>();
+            assert_eq!(expected, coverage.uncovered_points(0..=20, 0..=20, usize::MAX), "{coverage:?}");
+            assert_eq!(21 * 21 - expected.len() as i64, coverage.covered_area(0..=20, 0..=20));
+            assert_eq!(expected.len().min(3), coverage.uncovered_points(0..=20, 0..=20, 3).len());
+        }
+
+        // Flat regions, where holes can have their corners on the top and bottom borders only
+        for _ in 0..3000 {
+            let diamonds = (0..rng.gen_range(1..4))
+                .map(|_| Diamond::new((rng.gen_range(-40..100), rng.gen_range(-40..10)), rng.gen_range(0..80)))
+                .collect::<Vec<_>>();
+            let coverage = Coverage::new(diamonds);
+            let expected = (0..=60).flat_map(|x| (0..=5).map(move |y| (x, y))).filter(|point| !coverage.contains(*point)).collect::<Vec<_>>();
+            assert_eq!(expected, coverage.uncovered_points(0..=60, 0..=5, usize::MAX), "{coverage:?}");
+        }
+        let coverage = Coverage::new(vec![Diamond::new((-30, -30), 71), Diamond::new((70, -30), 70)]);
+        let uncovered = coverage.uncovered_points(0..=60, 0..=5, usize::MAX);
+        assert_eq!(138, uncovered.len());
+        assert!(uncovered.contains(&(20, 0)));
+
+        // Nothing covered, the whole region is flood filled from a corner
+        assert_eq!(12, Coverage::default().uncovered_points(0..=3, 0..=2, 100).len());
+    }
+}
@@ -1,121 +1,65 @@
 use std::ops::RangeInclusive;
 use std::str::FromStr;
 
-use smallvec::{smallvec, SmallVec};
+use crate::j15::diamond::{Coverage, Diamond, Point};
 
-fn guess_at_line(s: &str, target_line: i32) -> usize {
-    let mut ranges: SmallVec<[RangeInclusive<i32>; 32]> = smallvec![];
-    let mut ranges_sec: SmallVec<[RangeInclusive<i32>; 32]> = smallvec![];
-    let mut beacons_column_on_lines: SmallVec<[i32; 32]> = smallvec![];
-    for line in s.lines() {
+pub mod diamond;
+
+// Multiplier of the x coordinate in the puzzle tuning frequency
+pub const TUNING_MULTIPLIER: i64 = 4_000_000;
+
+// (sensor, closest beacon) for every line
+#[allow(unused)]
+pub fn parse_sensors(s: &str) -> Vec<(Point, Point)> {
+    s.lines().map(|line| {
         let mut parts = line.split('=');
         parts.next().unwrap();
-        let x = i32::from_str(parts.next().unwrap().split(',').next().unwrap()).unwrap();
-        let y = i32::from_str(parts.next().unwrap().split(':').next().unwrap()).unwrap();
-        let b_x = i32::from_str(parts.next().unwrap().split(',').next().unwrap()).unwrap();
-        let b_y = i32::from_str(parts.next().unwrap()).unwrap();
-
-        let distance = (x - b_x).abs() + (y - b_y).abs();
-
-        let over_reach = (distance) - (y - target_line).abs();
-
-        if b_y == target_line && !beacons_column_on_lines.contains(&b_x) {
-            beacons_column_on_lines.push(b_x);
-        }
-
-        if over_reach < 0 {
-            continue;
-        }
-
-        let candidate_range = (x - over_reach)..=(x + over_reach);
-        ranges.push(candidate_range);
-        let mut old_count = ranges.len();
-        loop {
-            for candidate_range in ranges.drain(..) {
-                let mut replaced = false;
-                for range in ranges_sec.iter_mut() {
-                    if range.start() <= candidate_range.start() && range.end() >= candidate_range.start() ||
-                        candidate_range.start() <= range.start() && candidate_range.end() >= range.start() {
-                        let new_range = (*range.start().min(candidate_range.start()))..=*(range.end().max(candidate_range.end()));
-                        let _ = std::mem::replace(range, new_range);
-                        replaced = true;
-                        break;
-                    }
-                }
-                if !replaced {
-                    ranges_sec.push(candidate_range);
-                }
-            }
-            std::mem::swap(&mut ranges, &mut ranges_sec);
-            if old_count == ranges.len() {
-                break;
-            }
-            old_count = ranges.len();
-        }
-    }
-    ranges.into_iter().flatten().count() - beacons_column_on_lines.len()
+        let x = i64::from_str(parts.next().unwrap().split(',').next().unwrap()).unwrap();
+        let y = i64::from_str(parts.next().unwrap().split(':').next().unwrap()).unwrap();
+        let b_x = i64::from_str(parts.next().unwrap().split(',').next().unwrap()).unwrap();
+        let b_y = i64::from_str(parts.next().unwrap()).unwrap();
+        ((x, y), (b_x, b_y))
+    }).collect()
 }
 
-fn guess_at_line_p2(s: &str, p2_range: RangeInclusive<i32>) -> usize {
-    let mut sonars: SmallVec<[(i32, i32, i32); 32]> = smallvec![];
-    for line in s.lines() {
-        let mut parts = line.split('=');
-        parts.next().unwrap();
-        let x = i32::from_str(parts.next().unwrap().split(',').next().unwrap()).unwrap();
-        let y = i32::from_str(parts.next().unwrap().split(':').next().unwrap()).unwrap();
-        let b_x = i32::from_str(parts.next().unwrap().split(',').next().unwrap()).unwrap();
-        let b_y = i32::from_str(parts.next().unwrap()).unwrap();
+#[allow(unused)]
+pub fn coverage_of(sensors: &[(Point, Point)]) -> Coverage {
+    Coverage::new(sensors.iter().map(|(sensor, beacon)| Diamond::from_sensor(*sensor, *beacon)).collect())
+}
 
-        let range = (x - b_x).abs() + (y - b_y).abs();
-        sonars.push((y, x, range));
-    }
+#[allow(unused)]
+pub fn parse_coverage(s: &str) -> Coverage {
+    coverage_of(&parse_sensors(s))
+}
 
-    for &(s1_y, s1_x, range1) in sonars.iter() {
-        for &(s2_y, s2_x, range2) in sonars.iter() {
-            if s2_x - s1_x + s2_y - s1_y == range1 + range2 + 2 {
-                'vert: for it in 0..=(range1 + 1) {
-                    let col = it + s1_x;
-                    let row = range1 + 1 - it + s1_y;
-
-                    for &(s_row, s_col, range) in sonars.iter() {
-                        if range >= (s_row - row).abs() + (s_col - col).abs()
-                            || !p2_range.contains(&row)
-                            || !p2_range.contains(&col)
-                        {
-                            continue 'vert;
-                        }
-                    }
-
-                    return col as usize * 4_000_000 + row as usize;
-                }
-            }
-
-            if s2_x - s1_x + s1_y - s2_y == range1 + range2 + 2 {
-                'vert: for it in 0..=(range1 + 1) {
-                    let col = it + s1_x;
-                    let row = -range1 - 1 + it + s1_y;
-
-                    for &(s_row, s_col, range) in sonars.iter() {
-                        if range >= (s_row - row).abs() + (s_col - col).abs()
-                            || !p2_range.contains(&row)
-                            || !p2_range.contains(&col)
-                        {
-                            continue 'vert;
-                        }
-                    }
-
-                    return col as usize * 4_000_000 + row as usize;
-                }
-            }
-        }
-    }
+#[allow(unused)]
+pub fn tuning_frequency((x, y): Point, multiplier: i64) -> i64 {
+    x * multiplier + y
+}
+
+fn guess_at_line(s: &str, target_line: i64) -> usize {
+    let sensors = parse_sensors(s);
+    let coverage = coverage_of(&sensors);
+    // Beacons always lie within the covered columns
+    let mut beacons_column_on_line = sensors.iter()
+        .filter(|(_, (_, b_y))| *b_y == target_line)
+        .map(|(_, (b_x, _))| *b_x)
+        .collect::<Vec<_>>();
+    beacons_column_on_line.sort_unstable();
+    beacons_column_on_line.dedup();
+    coverage.covered_in_row(target_line) as usize - beacons_column_on_line.len()
+}
 
-    panic!()
+// The only uncovered point of the search space
+#[allow(unused)]
+pub fn distress_beacon(s: &str, search_space: RangeInclusive<i64>) -> Option<Point> {
+    let uncovered = parse_coverage(s).uncovered_points(search_space.clone(), search_space, 2);
+    if uncovered.len() == 1 { Some(uncovered[0]) } else { None }
 }
 
 #[allow(unused)]
 pub fn _p1(s: &str, target_line: i32) -> usize {
-    guess_at_line(s, target_line)
+    guess_at_line(s, target_line as i64)
 }
 
 #[allow(unused)]
@@ -125,7 +69,14 @@ pub fn p1() -> usize {
 
 #[allow(unused)]
 pub fn _p2(s: &str, search_space: RangeInclusive<i32>) -> usize {
-    guess_at_line_p2(s, search_space)
+    _p2_with_multiplier(s, search_space, TUNING_MULTIPLIER).unwrap()
+}
+
+// None unless exactly one point of the search space is uncovered
+#[allow(unused)]
+pub fn _p2_with_multiplier(s: &str, search_space: RangeInclusive<i32>, multiplier: i64) -> Option<usize> {
+    let search_space = (*search_space.start() as i64)..=(*search_space.end() as i64);
+    distress_beacon(s, search_space).map(|beacon| tuning_frequency(beacon, multiplier) as usize)
 }
 
 #[allow(unused)]
@@ -151,5 +102,21 @@ mod j15_tests {
     fn test_p2() {
         assert_eq!(56000011, _p2(include_str!("j15_test.txt"), 0..=20));
         assert_eq!(12518502636475, _p2(include_str!("j15.txt"), 0..=4000000));
+        assert_eq!(Some(151), _p2_with_multiplier(include_str!("j15_test.txt"), 0..=20, 10));
+        // Around the sensors, more than one point is left uncovered
+        assert_eq!(None, _p2_with_multiplier(include_str!("j15_test.txt"), -10..=30, 10));
+        assert_eq!(None, _p2_with_multiplier(include_str!("j15_test.txt"), 0..=10, 10));
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_coverage() {
+        let coverage = parse_coverage(include_str!("j15_test.txt"));
+        assert_eq!(14, coverage.diamonds().len());
+        assert_eq!(vec![(-2, 24)], coverage.row_intervals(10));
+        assert_eq!(vec![(14, 11)], coverage.uncovered_points(0..=20, 0..=20, 100));
+        assert_eq!(21 * 21 - 1, coverage.covered_area(0..=20, 0..=20));
+        assert_eq!(Some((14, 11)), distress_beacon(include_str!("j15_test.txt"), 0..=20));
+        assert_eq!(None, distress_beacon(include_str!("j15_test.txt"), 0..=30));
     }
 }
\ No newline at end of file
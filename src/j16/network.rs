@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use crate::search::bfs;
+
+#[allow(unused)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Valve {
+    pub name: String,
+    pub flow: u32,
+    pub tunnels: Vec<usize>,
+}
+
+#[allow(unused)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ValveError {
+    UnexpectedLine { line: usize },
+    InvalidFlow { line: usize, flow: String },
+    UnknownValve { line: usize, name: String },
+    TooManyValves { count: usize },
+}
+
+impl Display for ValveError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValveError::UnexpectedLine { line } => write!(f, "line {line}: expected a valve description"),
+            ValveError::InvalidFlow { line, flow } => write!(f, "line {line}: invalid flow rate {flow}"),
+            ValveError::UnknownValve { line, name } => write!(f, "line {line}: there is no valve {name}"),
+            ValveError::TooManyValves { count } => write!(f, "{count} valves with a positive flow, at most {MAX_USEFUL_VALVES} are supported"),
+        }
+    }
+}
+
+impl std::error::Error for ValveError {}
+
+// Opened valves are kept as a bitmask and the solver has a table entry per
+// subset of them. Splitting the valves between two agents goes through every
+// subset of every subset, 3^n of them, and each agent tries every order it
+// has time for : 16 valves keep both within seconds
+pub const MAX_USEFUL_VALVES: usize = 16;
+
+// Someone opening valves, from `start` with `time` minutes left
+#[allow(unused)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Agent {
+    pub start: usize,
+    pub time: usize,
+}
+
+// Valves opened by each agent, in order
+#[allow(unused)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Solution {
+    pub released: u32,
+    pub routes: Vec<Vec<usize>>,
+}
+
+// The valves, and the shortest distances between them. Only the valves with
+// a positive flow matter once the distances are known : they are the bits
+// of the opened sets
+#[allow(unused)]
+#[derive(Clone, Debug)]
+pub struct ValveNetwork {
+    valves: Vec<Valve>,
+    ids: HashMap<String, usize>,
+    distances: Vec<Vec<Option<usize>>>,
+    useful: Vec<usize>,
+}
+
+#[allow(unused)]
+impl ValveNetwork {
+    pub fn parse(s: &str) -> Result<Self, ValveError> {
+        let mut descriptions = vec![];
+        for (idx, line) in s.lines().enumerate() {
+            let line_number = idx + 1;
+            let unexpected = || ValveError::UnexpectedLine { line: line_number };
+            let rest = line.strip_prefix("Valve ").ok_or_else(unexpected)?;
+            let (name, rest) = rest.split_once(" has flow rate=").ok_or_else(unexpected)?;
+            let (flow, rest) = rest.split_once(';').ok_or_else(unexpected)?;
+            let flow = u32::from_str(flow).map_err(|_| ValveError::InvalidFlow { line: line_number, flow: flow.to_string() })?;
+            let tunnels = rest.strip_prefix(" tunnels lead to valves ")
+                .or_else(|| rest.strip_prefix(" tunnel leads to valve "))
+                .ok_or_else(unexpected)?;
+            descriptions.push((line_number, name, flow, tunnels.split(", ").collect::<Vec<_>>()));
+        }
+
+        let ids = descriptions.iter().enumerate()
+            .map(|(id, (_, name, _, _))| (name.to_string(), id))
+            .collect::<HashMap<_, _>>();
+        let mut valves = vec![];
+        for (line, name, flow, tunnels) in descriptions {
+            let tunnels = tunnels.into_iter()
+                .map(|tunnel| ids.get(tunnel).copied().ok_or_else(|| ValveError::UnknownValve { line, name: tunnel.to_string() }))
+                .collect::<Result<Vec<_>, _>>()?;
+            valves.push(Valve { name: name.to_string(), flow, tunnels });
+        }
+
+        let useful = (0..valves.len()).filter(|id| valves[*id].flow > 0).collect::<Vec<_>>();
+        if useful.len() > MAX_USEFUL_VALVES {
+            return Err(ValveError::TooManyValves { count: useful.len() });
+        }
+        let distances = (0..valves.len()).map(|start| {
+            let search = bfs([start], |valve| valves[*valve].tunnels.clone(), |_| false);
+            (0..valves.len()).map(|end| search.cost_to(&end)).collect()
+        }).collect();
+        Ok(Self { valves, ids, distances, useful })
+    }
+
+    pub fn valves(&self) -> &[Valve] {
+        &self.valves
+    }
+
+    pub fn id(&self, name: &str) -> Option<usize> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn distance(&self, from: usize, to: usize) -> Option<usize> {
+        self.distances[from][to]
+    }
+
+    // Valves with a positive flow, bit `i` of an opened set is `useful()[i]`
+    pub fn useful(&self) -> &[usize] {
+        &self.useful
+    }
+
+    pub fn agent(&self, start: &str, time: usize) -> Option<Agent> {
+        self.id(start).map(|start| Agent { start, time })
+    }
+
+    // Valves of `allowed` which can be reached from `from` and opened in
+    // time, with the minutes left once open
+    fn moves(&self, from: usize, time: usize, allowed: usize) -> impl Iterator<Item=(usize, usize)> + '_ {
+        self.useful.iter().enumerate()
+            .filter(move |(bit, _)| allowed & (1 << bit) != 0)
+            .filter_map(move |(bit, valve)| match self.distances[from][*valve] {
+                Some(distance) if distance < time => Some((bit, time - distance - 1)),
+                _ => None,
+            })
+    }
+
+    fn explore(&self, at: usize, time: usize, opened: usize, released: u32, best: &mut [u32]) {
+        best[opened] = best[opened].max(released);
+        let full = (1 << self.useful.len()) - 1;
+        for (bit, time_left) in self.moves(at, time, full & !opened) {
+            let valve = self.useful[bit];
+            self.explore(valve, time_left, opened | (1 << bit), released + self.valves[valve].flow * time_left as u32, best);
+        }
+    }
+
+    // Most pressure `agent` alone releases opening valves of each set, any
+    // subset of them included
+    fn best_per_set(&self, agent: Agent) -> Vec<u32> {
+        let mut best = vec![0; 1 << self.useful.len()];
+        self.explore(agent.start, agent.time, 0, 0, &mut best);
+        for bit in 0..self.useful.len() {
+            for set in 0..best.len() {
+                if set & (1 << bit) != 0 {
+                    best[set] = best[set].max(best[set ^ (1 << bit)]);
+                }
+            }
+        }
+        best
+    }
+
+    // Best order to open valves of `allowed`
+    fn best_route(&self, agent: Agent, allowed: usize) -> (u32, Vec<usize>) {
+        fn rec(network: &ValveNetwork, at: usize, time: usize, allowed: usize, released: u32,
+               route: &mut Vec<usize>, best: &mut (u32, Vec<usize>)) {
+            if released > best.0 {
+                *best = (released, route.clone());
+            }
+            for (bit, time_left) in network.moves(at, time, allowed) {
+                let valve = network.useful[bit];
+                route.push(valve);
+                rec(network, valve, time_left, allowed & !(1 << bit), released + network.valves[valve].flow * time_left as u32, route, best);
+                route.pop();
+            }
+        }
+        let mut best = (0, vec![]);
+        rec(self, agent.start, agent.time, allowed, 0, &mut vec![], &mut best);
+        best
+    }
+
+    // Splits the valves between the agents : after agent `k` the table holds
+    // the most pressure the first `k` agents release with each set of valves
+    pub fn solve(&self, agents: &[Agent]) -> Solution {
+        let full = (1usize << self.useful.len()) - 1;
+        let mut tables: Vec<Vec<u32>> = vec![];
+        let mut agent_best = vec![];
+        for agent in agents.iter() {
+            let best = self.best_per_set(*agent);
+            let table = match tables.last() {
+                None => best.clone(),
+                Some(previous) => (0..=full).map(|set| {
+                    let mut result = 0;
+                    let mut others = set;
+                    loop {
+                        result = result.max(previous[others] + best[set ^ others]);
+                        if others == 0 {
+                            break result;
+                        }
+                        others = (others - 1) & set;
+                    }
+                }).collect(),
+            };
+            tables.push(table);
+            agent_best.push(best);
+        }
+
+        let released = tables.last().map_or(0, |table| table[full]);
+        // Walks the tables back to the set each agent was given
+        let mut sets = vec![0; agents.len()];
+        let mut set = full;
+        for k in (0..agents.len()).rev() {
+            if k == 0 {
+                sets[k] = set;
+                break;
+            }
+            let mut others = set;
+            loop {
+                if tables[k - 1][others] + agent_best[k][set ^ others] == tables[k][set] {
+                    break;
+                }
+                others = (others - 1) & set;
+            }
+            sets[k] = set ^ others;
+            set = others;
+        }
+        let routes = agents.iter().zip(sets)
+            .map(|(agent, set)| self.best_route(*agent, set).1)
+            .collect();
+        Solution { released, routes }
+    }
+}
+
+#[cfg(test)]
+#[allow(unused)]
+mod network_tests {
+    #[allow(unused)]
+    use super::*;
+
+    #[test]
+    #[allow(unused)]
+    fn test_parse() {
+        let network = ValveNetwork::parse(include_str!("j16_test.txt")).unwrap();
+        assert_eq!(10, network.valves().len());
+        assert_eq!(6, network.useful().len());
+        let (aa, hh) = (network.id("AA").unwrap(), network.id("HH").unwrap());
+        assert_eq!(Some(5), network.distance(aa, hh));
+        assert_eq!(vec![network.id("GG").unwrap()], network.valves()[hh].tunnels);
+
+        assert_eq!(Err(ValveError::UnknownValve { line: 1, name: "BB".to_string() }).map(|_: ValveNetwork| ()),
+                   ValveNetwork::parse("Valve AA has flow rate=0; tunnel leads to valve BB").map(|_| ()));
+        assert_eq!(Err(ValveError::InvalidFlow { line: 2, flow: "x".to_string() }).map(|_: ValveNetwork| ()),
+                   ValveNetwork::parse("Valve AA has flow rate=0; tunnel leads to valve AA\nValve BB has flow rate=x; tunnel leads to valve AA").map(|_| ()));
+        assert_eq!(Err(ValveError::UnexpectedLine { line: 1 }).map(|_: ValveNetwork| ()),
+                   ValveNetwork::parse("Valve AA has flow rate=0").map(|_| ()));
+        let many = (0..=MAX_USEFUL_VALVES).map(|id| format!("Valve V{id} has flow rate=1; tunnel leads to valve V0")).collect::<Vec<_>>().join("\n");
+        assert_eq!(Err(ValveError::TooManyValves { count: MAX_USEFUL_VALVES + 1 }).map(|_: ValveNetwork| ()),
+                   ValveNetwork::parse(&many).map(|_| ()));
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_solve() {
+        let network = ValveNetwork::parse(include_str!("j16_test.txt")).unwrap();
+        let names = |route: &Vec<usize>| route.iter().map(|valve| network.valves()[*valve].name.as_str()).collect::<Vec<_>>();
+
+        let solution = network.solve(&[network.agent("AA", 30).unwrap()]);
+        assert_eq!(1651, solution.released);
+        assert_eq!(vec!["DD", "BB", "JJ", "HH", "EE", "CC"], names(&solution.routes[0]));
+
+        let solution = network.solve(&[network.agent("AA", 26).unwrap(), network.agent("AA", 26).unwrap()]);
+        assert_eq!(1707, solution.released);
+        assert_eq!(vec!["JJ", "BB", "CC"], names(&solution.routes[0]));
+        assert_eq!(vec!["DD", "HH", "EE"], names(&solution.routes[1]));
+
+        // An agent without time changes nothing, nor does one more agent once every valve is open
+        assert_eq!(1651, network.solve(&[network.agent("AA", 30).unwrap(), network.agent("JJ", 0).unwrap()]).released);
+        let three = network.solve(&[network.agent("AA", 26).unwrap(), network.agent("AA", 26).unwrap(), network.agent("HH", 26).unwrap()]);
+        assert!(three.released >= 1707);
+        assert_eq!(3, three.routes.len());
+        assert_eq!(0, network.solve(&[]).released);
+
+        // Each route releases its share of the total
+        let released = |agent: Agent, route: &Vec<usize>| {
+            let (mut at, mut time, mut released) = (agent.start, agent.time, 0);
+            for valve in route {
+                time -= network.distance(at, *valve).unwrap() + 1;
+                released += network.valves()[*valve].flow * time as u32;
+                at = *valve;
+            }
+            released
+        };
+        let agents = [network.agent("AA", 26).unwrap(), network.agent("AA", 26).unwrap(), network.agent("HH", 26).unwrap()];
+        assert_eq!(three.released, agents.iter().zip(three.routes.iter()).map(|(agent, route)| released(*agent, route)).sum::<u32>());
+    }
+}
@@ -0,0 +1,196 @@
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use crate::j16::network::{Agent, Solution, ValveNetwork};
+use crate::search::bfs;
+
+#[allow(unused)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Action {
+    MoveTo(usize),
+    Open(usize),
+    Wait,
+}
+
+// What an agent does during `minute`, counted from 1, and the pressure its
+// valves released by the end of it
+#[allow(unused)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Step {
+    pub minute: usize,
+    pub action: Action,
+    pub released: u32,
+}
+
+#[allow(unused)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Plan {
+    pub agents: Vec<Agent>,
+    pub solution: Solution,
+    pub timelines: Vec<Vec<Step>>,
+}
+
+// Colors of the agents in the DOT export, reused past the last one
+const AGENT_COLORS: [&str; 6] = ["red", "blue", "darkgreen", "orange", "purple", "brown"];
+
+#[allow(unused)]
+impl ValveNetwork {
+    // Shortest walk between two valves, `from` excluded
+    fn walk(&self, from: usize, to: usize) -> Vec<usize> {
+        let search = bfs([from], |valve| self.valves()[*valve].tunnels.clone(), |valve| *valve == to);
+        search.path().map(|path| path[1..].to_vec()).unwrap_or_default()
+    }
+
+    // Every minute of `agent` opening the valves of `route` in order, then waiting
+    pub fn timeline(&self, agent: Agent, route: &[usize]) -> Vec<Step> {
+        let mut actions = vec![];
+        let mut at = agent.start;
+        for valve in route {
+            actions.extend(self.walk(at, *valve).into_iter().map(Action::MoveTo));
+            actions.push(Action::Open(*valve));
+            at = *valve;
+        }
+        actions.resize(actions.len().max(agent.time), Action::Wait);
+
+        let (mut flow, mut released) = (0, 0);
+        actions.into_iter().take(agent.time).enumerate().map(|(idx, action)| {
+            released += flow;
+            if let Action::Open(valve) = action {
+                flow += self.valves()[valve].flow;
+            }
+            Step { minute: idx + 1, action, released }
+        }).collect()
+    }
+
+    pub fn plan(&self, agents: &[Agent]) -> Plan {
+        let solution = self.solve(agents);
+        let timelines = agents.iter().zip(solution.routes.iter())
+            .map(|(agent, route)| self.timeline(*agent, route))
+            .collect();
+        Plan { agents: agents.to_vec(), solution, timelines }
+    }
+
+    // Valves labelled with their flow, the ones with a positive flow drawn
+    // bold. With a plan, the tunnels walked and the valves opened take the
+    // color of their agent
+    pub fn to_dot(&self, plan: Option<&Plan>) -> String {
+        let mut walked: Vec<HashSet<(usize, usize)>> = vec![];
+        let mut opened = vec![None; self.valves().len()];
+        for (agent, timeline) in plan.iter().flat_map(|plan| plan.agents.iter().zip(plan.timelines.iter())) {
+            let mut edges = HashSet::new();
+            let mut at = agent.start;
+            for step in timeline {
+                match step.action {
+                    Action::MoveTo(valve) => {
+                        edges.insert((at.min(valve), at.max(valve)));
+                        at = valve;
+                    }
+                    Action::Open(valve) => opened[valve] = Some(walked.len()),
+                    Action::Wait => {}
+                }
+            }
+            walked.push(edges);
+        }
+        let color = |agent: usize| AGENT_COLORS[agent % AGENT_COLORS.len()];
+
+        let mut dot = String::from("graph valves {\n");
+        for (id, valve) in self.valves().iter().enumerate() {
+            let mut attributes = format!("label=\"{}\\n{}\"", valve.name, valve.flow);
+            match (valve.flow > 0, opened[id]) {
+                (_, Some(agent)) => write!(attributes, ", style=\"bold,filled\", fillcolor={}, fontcolor=white", color(agent)).unwrap(),
+                (true, None) => attributes.push_str(", style=bold"),
+                (false, None) => {}
+            }
+            writeln!(dot, "  {} [{attributes}];", valve.name).unwrap();
+        }
+        for (id, valve) in self.valves().iter().enumerate() {
+            for tunnel in valve.tunnels.iter().filter(|tunnel| **tunnel > id) {
+                let colors = (0..walked.len()).filter(|agent| walked[*agent].contains(&(id, *tunnel))).map(color).collect::<Vec<_>>();
+                if colors.is_empty() {
+                    writeln!(dot, "  {} -- {};", valve.name, self.valves()[*tunnel].name).unwrap();
+                } else {
+                    writeln!(dot, "  {} -- {} [color=\"{}\", penwidth=3];", valve.name, self.valves()[*tunnel].name, colors.join(":")).unwrap();
+                }
+            }
+        }
+        dot.push('}');
+        dot.push('\n');
+        dot
+    }
+}
+
+#[allow(unused)]
+impl Plan {
+    pub fn released(&self) -> u32 {
+        self.timelines.iter().filter_map(|timeline| timeline.last()).map(|step| step.released).sum()
+    }
+
+    // One line per minute and agent, like "Minute 2, agent 1: open DD (0 released)"
+    pub fn describe(&self, network: &ValveNetwork) -> String {
+        let name = |valve: usize| network.valves()[valve].name.as_str();
+        let minutes = self.timelines.iter().map(|timeline| timeline.len()).max().unwrap_or(0);
+        let mut description = String::new();
+        for minute in 0..minutes {
+            for (agent, timeline) in self.timelines.iter().enumerate() {
+                if let Some(step) = timeline.get(minute) {
+                    let action = match step.action {
+                        Action::MoveTo(valve) => format!("move to {}", name(valve)),
+                        Action::Open(valve) => format!("open {}", name(valve)),
+                        Action::Wait => "wait".to_string(),
+                    };
+                    writeln!(description, "Minute {}, agent {}: {action} ({} released)", step.minute, agent + 1, step.released).unwrap();
+                }
+            }
+        }
+        description
+    }
+}
+
+#[cfg(test)]
+#[allow(unused)]
+mod plan_tests {
+    #[allow(unused)]
+    use super::*;
+
+    #[test]
+    #[allow(unused)]
+    fn test_plan() {
+        let network = ValveNetwork::parse(include_str!("j16_test.txt")).unwrap();
+        let id = |name: &str| network.id(name).unwrap();
+        let plan = network.plan(&[network.agent("AA", 30).unwrap()]);
+        assert_eq!(1651, plan.released());
+        let timeline = &plan.timelines[0];
+        assert_eq!(30, timeline.len());
+        assert_eq!(vec![Action::MoveTo(id("DD")), Action::Open(id("DD")), Action::MoveTo(id("CC")), Action::MoveTo(id("BB")), Action::Open(id("BB"))],
+                   timeline.iter().take(5).map(|step| step.action).collect::<Vec<_>>());
+        // DD releases 20 from minute 3 on
+        assert_eq!(vec![0, 0, 20, 40, 60, 93], timeline.iter().take(6).map(|step| step.released).collect::<Vec<_>>());
+        assert_eq!(Action::Wait, timeline[29].action);
+        assert!(plan.describe(&network).starts_with("Minute 1, agent 1: move to DD (0 released)\nMinute 2, agent 1: open DD (0 released)\n"));
+
+        let plan = network.plan(&[network.agent("AA", 26).unwrap(), network.agent("AA", 26).unwrap()]);
+        assert_eq!(1707, plan.released());
+        assert_eq!(plan.solution.released, plan.released());
+        assert_eq!(Action::MoveTo(id("II")), plan.timelines[0][0].action);
+        assert_eq!(Action::MoveTo(id("DD")), plan.timelines[1][0].action);
+        assert_eq!(2 * 26, plan.describe(&network).lines().count());
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_to_dot() {
+        let network = ValveNetwork::parse(include_str!("j16_test.txt")).unwrap();
+        let dot = network.to_dot(None);
+        assert!(dot.starts_with("graph valves {\n  AA [label=\"AA\\n0\"];\n  BB [label=\"BB\\n13\", style=bold];\n"));
+        assert!(dot.contains("  AA -- DD;\n"));
+        assert_eq!(10, dot.matches(" -- ").count());
+
+        let plan = network.plan(&[network.agent("AA", 26).unwrap(), network.agent("AA", 26).unwrap()]);
+        let dot = network.to_dot(Some(&plan));
+        assert!(dot.contains("  AA -- II [color=\"red\", penwidth=3];\n"));
+        assert!(dot.contains("  AA -- DD [color=\"blue\", penwidth=3];\n"));
+        assert!(dot.contains("  HH [label=\"HH\\n22\", style=\"bold,filled\", fillcolor=blue, fontcolor=white];\n"));
+        assert!(dot.contains("  FF -- GG [color=\"blue\", penwidth=3];\n"));
+        assert!(dot.ends_with("}\n"));
+    }
+}
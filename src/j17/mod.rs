@@ -1,8 +1,15 @@
 use std::fmt::{Display, Formatter, Write};
 
-use smallvec::{SmallVec, smallvec};
+use smallvec::SmallVec;
 
-#[derive(Eq, PartialEq, Debug)]
+use crate::cycle::{brent_by_key, Cycle, fingerprint};
+use crate::j17::rules::Rules;
+use crate::j17::state::TetrisError;
+
+pub mod rules;
+pub mod state;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
 enum CellType {
     Empty,
     Fixed,
@@ -17,98 +24,102 @@ impl Display for CellType {
     }
 }
 
-const EXPECTED_MAX_ROWS: usize = 20000;
+#[allow(unused)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+pub enum Jet {
+    Left,
+    Right,
+}
 
-const CHECK_PERIOD: usize = 500;
+#[allow(unused)]
+pub fn parse_jets(s: &str) -> Result<Vec<Jet>, char> {
+    s.chars().map(|c| match c {
+        '<' => Ok(Jet::Left),
+        '>' => Ok(Jet::Right),
+        _ => Err(c),
+    }).collect()
+}
+
+// Rows kept in the chamber before the ones no rock can reach anymore are
+// dropped, and added before trying again
+const MAX_ROWS: usize = 4096;
 
-fn simulation(s: &str, num_rocks: usize) -> usize {
-    let mut y_top_history: SmallVec<[usize; 200]> = smallvec![];
+// Rows of the tower compared to find when the game repeats itself
+pub const FINGERPRINT_ROWS: usize = 32;
+
+// The game, one rock at a time
+#[allow(unused)]
+#[derive(Clone, Debug)]
+pub struct Tetris {
+    rules: Rules,
+    jets: Vec<Jet>,
+    // Rows from the bottom, above the `dropped_rows` ones
+    chamber: Vec<Vec<CellType>>,
+    dropped_rows: usize,
+    top_y: usize,
+    rocks: usize,
+    jet: usize,
+    // Chamber length at which unreachable rows are next looked for
+    drop_at: usize,
+}
 
-    let mut chamber: SmallVec<[SmallVec<[CellType; 7]>; EXPECTED_MAX_ROWS]> = smallvec![];
-    for row in 0..EXPECTED_MAX_ROWS {
-        chamber.push(smallvec![]);
-        for _ in 0..7 {
-            chamber[row].push(CellType::Empty);
+#[allow(unused)]
+impl Tetris {
+    // `s` holds the jets on a single line
+    pub fn new(s: &str, rules: Rules) -> Result<Self, TetrisError> {
+        let jets = parse_jets(s.trim_end()).map_err(|c| TetrisError::InvalidJet { line: 1, c })?;
+        if jets.is_empty() {
+            return Err(TetrisError::UnexpectedLine { line: 1, expected: "the jets" });
         }
+        Ok(Self { rules, jets, chamber: vec![], dropped_rows: 0, top_y: 0, rocks: 0, jet: 0, drop_at: MAX_ROWS })
     }
 
-    let mut rock_count = 0;
-
-    let mut commands = s.chars().peekable();
+    pub fn rules(&self) -> &Rules {
+        &self.rules
+    }
 
-    let mut old_top_y = 0;
-    let mut top_y = 0;
-    let mut dropped_lines = 0;
+    pub fn height(&self) -> usize {
+        self.top_y + self.dropped_rows
+    }
 
-    let mut rock_type = 0;
+    // Rocks dropped so far
+    pub fn rocks(&self) -> usize {
+        self.rocks
+    }
 
-    let mut falling_rock_cells: SmallVec<[(usize, usize); 5]> = smallvec![];
+    fn is_empty(&self, (row, col): (usize, usize)) -> bool {
+        self.chamber[row][col] == CellType::Empty
+    }
 
-    while rock_count < num_rocks {
-        // Spawn rock
-        match rock_type {
-            0 => {
-                for col in 2usize..(2 + 4) {
-                    falling_rock_cells.push((top_y + 3, col));
-                }
-            }
-            1 => {
-                falling_rock_cells.push((top_y + 3, 2 + 1));
-                falling_rock_cells.push((top_y + 4, 2));
-                falling_rock_cells.push((top_y + 4, 2 + 1));
-                falling_rock_cells.push((top_y + 4, 2 + 2));
-                falling_rock_cells.push((top_y + 5, 2 + 1));
-            }
-            2 => {
-                falling_rock_cells.push((top_y + 3, 2));
-                falling_rock_cells.push((top_y + 3, 2 + 1));
-                falling_rock_cells.push((top_y + 3, 2 + 2));
-                falling_rock_cells.push((top_y + 4, 2 + 2));
-                falling_rock_cells.push((top_y + 5, 2 + 2));
-            }
-            3 => {
-                falling_rock_cells.push((top_y + 3, 2));
-                falling_rock_cells.push((top_y + 4, 2));
-                falling_rock_cells.push((top_y + 5, 2));
-                falling_rock_cells.push((top_y + 6, 2));
-            }
-            4 => {
-                falling_rock_cells.push((top_y + 3, 2));
-                falling_rock_cells.push((top_y + 3, 3));
-                falling_rock_cells.push((top_y + 4, 2));
-                falling_rock_cells.push((top_y + 4, 3));
-            }
-            _ => panic!()
+    pub fn drop_rock(&mut self) {
+        let width = self.rules.width();
+        let mut falling_rock_cells: SmallVec<[(usize, usize); 5]> = self.rules.spawn(self.rocks, self.top_y).collect();
+        let top = falling_rock_cells.iter().map(|(row, _)| row + 1).max().unwrap();
+        while self.chamber.len() < top {
+            self.chamber.push(vec![CellType::Empty; width]);
         }
 
-        // Fall rock
         loop {
-            match commands.next() {
-                Some('>') => {
-                    if falling_rock_cells.iter().all(|(row, col)| *col + 1 < 7 &&
-                        chamber[*row][(*col + 1)] == CellType::Empty) {
+            let jet = self.jets[self.jet];
+            self.jet = (self.jet + 1) % self.jets.len();
+            match jet {
+                Jet::Right => {
+                    if falling_rock_cells.iter().all(|(row, col)| *col + 1 < width && self.is_empty((*row, *col + 1))) {
                         for (_, col) in falling_rock_cells.iter_mut() {
                             *col += 1;
                         }
                     }
                 }
-                Some('<') => {
-                    if falling_rock_cells.iter().all(|(row, col)| *col > 0 &&
-                        chamber[*row][(*col - 1)] == CellType::Empty) {
+                Jet::Left => {
+                    if falling_rock_cells.iter().all(|(row, col)| *col > 0 && self.is_empty((*row, *col - 1))) {
                         for (_, col) in falling_rock_cells.iter_mut() {
                             *col -= 1;
                         }
                     }
                 }
-                None => {
-                    commands = s.chars().peekable();
-                    continue;
-                }
-                _ => panic!()
             }
 
-            if falling_rock_cells.iter().all(|(row, col)| *row > 0 &&
-                chamber[*row - 1][*col] == CellType::Empty) {
+            if falling_rock_cells.iter().all(|(row, col)| *row > 0 && self.is_empty((*row - 1, *col))) {
                 for (row, _) in falling_rock_cells.iter_mut() {
                     *row -= 1;
                 }
@@ -119,108 +130,131 @@ fn simulation(s: &str, num_rocks: usize) -> usize {
 
         // Fix cells
         for (row, col) in falling_rock_cells.drain(..) {
-            chamber[row][col] = CellType::Fixed;
-            top_y = top_y.max(row + 1);
+            self.chamber[row][col] = CellType::Fixed;
+            self.top_y = self.top_y.max(row + 1);
         }
+        self.rocks += 1;
 
-        // Update rock type
-        rock_type = (rock_type + 1) % 5;
-
-        rock_count += 1;
+        if self.chamber.len() >= self.drop_at {
+            self.drop_unreachable_rows();
+            self.drop_at = self.chamber.len() + MAX_ROWS;
+        }
+    }
 
-        if top_y >= EXPECTED_MAX_ROWS - 10 {
-            let mut mask = [false; 7];
-            let mut max_row = 0;
+    pub fn drop_rocks(&mut self, count: usize) {
+        for _ in 0..count {
+            self.drop_rock();
+        }
+    }
 
-            for row in (0..top_y).rev() {
-                for (col, m) in mask.iter_mut().enumerate() {
-                    if chamber[row][col] == CellType::Fixed {
-                        *m = true;
+    // Rocks fall from above the top and only ever move through empty cells,
+    // so the empty cells connected to the top are the only ones they can
+    // reach. Rows below the lowest of them are dropped : the cells under
+    // that one are all fixed, and the new floor blocks rocks the same way
+    fn drop_unreachable_rows(&mut self) {
+        let width = self.rules.width();
+        let mut reached = vec![false; self.top_y * width];
+        let mut stack = vec![];
+        if let Some(top) = self.top_y.checked_sub(1) {
+            stack.extend((0..width).map(|col| (top, col)).filter(|cell| self.is_empty(*cell)));
+        }
+        let mut lowest = self.top_y;
+        while let Some((row, col)) = stack.pop() {
+            if reached[row * width + col] {
+                continue;
+            }
+            reached[row * width + col] = true;
+            lowest = lowest.min(row);
+            let neighbors = [(row.checked_sub(1), Some(col)), (Some(row + 1), Some(col)), (Some(row), col.checked_sub(1)), (Some(row), Some(col + 1))];
+            for neighbor in neighbors {
+                if let (Some(row), Some(col)) = neighbor {
+                    if row < self.top_y && col < width && !reached[row * width + col] && self.is_empty((row, col)) {
+                        stack.push((row, col));
                     }
                 }
-                if mask.iter().all(|elt| *elt) {
-                    max_row = row;
-                    break;
-                }
             }
+        }
 
-            for _ in chamber.drain(..max_row) {}
-            for _ in 0..max_row {
-                chamber.push(SmallVec::from([(); 7].map(|_| CellType::Empty)))
-            }
-            top_y -= max_row;
+        self.chamber.drain(..lowest);
+        self.top_y -= lowest;
+        self.dropped_rows += lowest;
+    }
 
-            dropped_lines += max_row;
+    // The top `rows` rows like in the puzzle, down to the floor if it is
+    // that close
+    pub fn snapshot(&self, rows: usize) -> String {
+        let mut drawing = String::new();
+        for row in (self.top_y.saturating_sub(rows)..self.top_y).rev() {
+            drawing.push('|');
+            for cell in self.chamber[row].iter() {
+                write!(drawing, "{cell}").unwrap();
+            }
+            drawing.push_str("|\n");
+        }
+        if rows > self.top_y && self.dropped_rows == 0 {
+            writeln!(drawing, "+{}+", "-".repeat(self.rules.width())).unwrap();
         }
+        drawing
+    }
 
-        // Check for periodicity
-        if rock_count % CHECK_PERIOD == 0 {
-            y_top_history.push((top_y + dropped_lines) - old_top_y);
-
-            if y_top_history.len() >= 7 {
-                let first_window = &y_top_history[1..=3];
-                for idx in 4..(y_top_history.len() - 3) {
-                    if first_window == &y_top_history[idx..(idx + 3)] {
-                        let period = idx - 1;
-                        let period_counts = (num_rocks - CHECK_PERIOD) / CHECK_PERIOD;
-                        let div = period_counts / period;
-                        let remainder = period_counts % period;
-                        let total = y_top_history[0] + div * y_top_history[1..(1 + period)].iter().sum::<usize>()
-                            + y_top_history[1..(1 + remainder)].iter().sum::<usize>();
-
-                        return total;
-                    }
-                }
-            }
+    // Heights after each of the next `count` rocks
+    pub fn height_series(&mut self, count: usize) -> Vec<usize> {
+        (0..count).map(|_| {
+            self.drop_rock();
+            self.height()
+        }).collect()
+    }
 
-            old_top_y = top_y + dropped_lines
+    // `height_series` as `rock,height` lines, rocks counted from the start of the game
+    pub fn heights_csv(&mut self, count: usize) -> String {
+        let first = self.rocks;
+        let mut csv = String::from("rock,height\n");
+        for (idx, height) in self.height_series(count).into_iter().enumerate() {
+            writeln!(csv, "{},{height}", first + idx + 1).unwrap();
         }
+        csv
     }
 
-    top_y + dropped_lines
-}
+    // Next rock, next jet and the top rows, the floor counting as rock
+    pub fn key(&self, rows: usize) -> (usize, usize, u64) {
+        let floor = vec![CellType::Fixed; self.rules.width()];
+        let top_rows = (0..rows).map(|idx| self.top_y.checked_sub(idx + 1).map_or(&floor, |row| &self.chamber[row]));
+        (self.rocks % self.rules.shapes().len(), self.jet, fingerprint(top_rows))
+    }
 
-#[allow(unused)]
-fn print_chamber(chamber: &mut SmallVec<[CellType; 140000]>,
-                 falling_rock_cells: &SmallVec<[(usize, usize); 5]>) {
-    for row in 0..=10 {
-        let row = 10 - row;
-
-        for col in 0..7 {
-            if falling_rock_cells.contains(&(row, col)) {
-                print!("@");
-            } else {
-                print!("{}", match chamber[row * 7 + col] {
-                    CellType::Empty => '.',
-                    CellType::Fixed => '#',
-                });
-            }
-        }
-        println!();
+    // The first rocks before the game repeats, then how many rocks it takes
+    pub fn find_cycle(&self) -> Cycle {
+        brent_by_key(self, Tetris::drop_rock, |tetris| tetris.key(FINGERPRINT_ROWS))
     }
-    println!();
-}
 
-#[allow(unused)]
-fn print_chamber_part(chamber: &SmallVec<[SmallVec<[CellType; 7]>; EXPECTED_MAX_ROWS]>,
-                      start_row: usize,
-                      end_row: usize) {
-    for row in (start_row..=end_row).rev() {
-        for col in 0..7 {
-            print!("{}", match chamber[row][col] {
-                CellType::Empty => '.',
-                CellType::Fixed => '#',
-            });
+    // Height after `num_rocks` rocks, extrapolated from the cycle of the game
+    pub fn height_after(&self, num_rocks: usize) -> usize {
+        let cycle = self.find_cycle();
+        let known = num_rocks.min(cycle.start + cycle.length);
+        let mut tetris = self.clone();
+        let mut heights = vec![tetris.height()];
+        for _ in 0..known {
+            tetris.drop_rock();
+            heights.push(tetris.height());
         }
-        println!();
+        cycle.extrapolate(&heights, num_rocks)
     }
-    println!();
 }
 
+fn simulation(s: &str, num_rocks: usize) -> Result<usize, TetrisError> {
+    simulation_with(s, num_rocks, &Rules::default())
+}
+
+#[allow(unused)]
+pub fn simulation_with(s: &str, num_rocks: usize, rules: &Rules) -> Result<usize, TetrisError> {
+    let mut tetris = Tetris::new(s, rules.clone())?;
+    tetris.drop_rocks(num_rocks);
+    Ok(tetris.height())
+}
 
 #[allow(unused)]
 pub fn _p1(s: &str) -> usize {
-    simulation(s, 2022)
+    simulation(s, 2022).unwrap()
 }
 
 #[allow(unused)]
@@ -230,7 +264,7 @@ pub fn p1() -> usize {
 
 #[allow(unused)]
 pub fn _p2(s: &str) -> usize {
-    simulation(s, 1000000000000)
+    Tetris::new(s, Rules::default()).unwrap().height_after(1000000000000)
 }
 
 #[allow(unused)]
@@ -257,4 +291,90 @@ mod j17_tests {
         assert_eq!(1514285714288, _p2(include_str!("j17_test.txt")));
         assert_eq!(1561739130391, _p2(include_str!("j17.txt")));
     }
+
+    #[test]
+    #[allow(unused)]
+    fn test_rules() {
+        let jets = include_str!("j17_test.txt");
+        assert_eq!(3068, simulation_with(jets, 2022, &Rules::default()).unwrap());
+        // Rocks as wide as the chamber pile up one row each
+        assert_eq!(100, simulation_with(jets, 100, &Rules::parse("#", 1, 0, 3).unwrap()).unwrap());
+        assert_eq!(100, simulation_with(jets, 100, &Rules::parse("###", 3, 0, 0).unwrap()).unwrap());
+        assert_ne!(3068, simulation_with(jets, 2022, &Rules::parse(include_str!("rocks.txt"), 9, 2, 3).unwrap()).unwrap());
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_cycle() {
+        let tetris = Tetris::new(include_str!("j17_test.txt"), Rules::default()).unwrap();
+        let cycle = tetris.find_cycle();
+        assert_eq!(0, cycle.length % 5);
+        assert_eq!(3068, tetris.height_after(2022));
+
+        // Extrapolated heights match the simulation
+        let mut simulated = tetris.clone();
+        for n in 0..3000 {
+            if n % 97 == 0 {
+                assert_eq!(simulated.height(), tetris.height_after(n), "after {n} rocks");
+            }
+            simulated.drop_rock();
+        }
+
+        // Enough rocks for rows to be dropped from the chamber
+        let rules = Rules::parse("#", 1, 0, 0).unwrap();
+        assert_eq!(10000, simulation_with(">", 10000, &rules).unwrap());
+        assert_eq!(Cycle { start: 0, length: 1 }, Tetris::new(">", rules).unwrap().find_cycle());
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_drop_unreachable_rows() {
+        // Every column is blocked once going down, yet a rock can still slide under the top down to the fourth row
+        let mut tetris = Tetris::load("width 4\nspawn 0 3\nrocks 0\njet 0\ndropped 0\njets >\nshape #\n\
+            row |###.|\nrow |....|\nrow |..##|\nrow |#...|\nrow |####|\n").unwrap();
+        tetris.drop_unreachable_rows();
+        assert_eq!((5, 1), (tetris.height(), tetris.dropped_rows));
+        assert_eq!("|###.|\n|....|\n|..##|\n|#...|\n", tetris.snapshot(10));
+
+        // A column never filled keeps every row reachable, the simulation stays linear
+        let rules = Rules::parse("#", 2, 0, 0).unwrap();
+        assert_eq!(100000, simulation_with(">", 100000, &rules).unwrap());
+
+        // Rows are still dropped in the puzzle
+        let mut tetris = Tetris::new(include_str!("j17_test.txt"), Rules::default()).unwrap();
+        tetris.drop_rocks(10000);
+        assert!(tetris.dropped_rows > 0);
+        assert!(tetris.chamber.len() < 2 * MAX_ROWS);
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_snapshot_and_csv() {
+        let mut tetris = Tetris::new(include_str!("j17_test.txt"), Rules::default()).unwrap();
+        assert_eq!("+-------+\n", tetris.snapshot(3));
+        assert_eq!("rock,height\n1,1\n2,4\n3,6\n4,7\n", tetris.heights_csv(4));
+        tetris.drop_rocks(6);
+        assert_eq!("|....#..|
+|....#..|
+|....##.|
+|##..##.|
+|######.|
+|.###...|
+|..#....|
+|.####..|
+|....##.|
+|....##.|
+|....#..|
+|..#.#..|
+|..#.#..|
+|#####..|
+|..###..|
+|...#...|
+|..####.|
++-------+
+", tetris.snapshot(20));
+        assert_eq!(3, tetris.snapshot(3).lines().count());
+        assert_eq!(vec![18, 21, 23], tetris.height_series(3));
+        assert_eq!(13, tetris.rocks());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,177 @@
+use std::fmt::{Display, Formatter};
+
+// Cells of a rock as (row, col), rows counted up from its bottom
+#[allow(unused)]
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Shape {
+    pub cells: Vec<(usize, usize)>,
+    pub width: usize,
+    pub height: usize,
+}
+
+// The drawing `parse_shapes` reads, without a trailing newline
+impl Display for Shape {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for row in (0..self.height).rev() {
+            let line = (0..self.width).map(|col| if self.cells.contains(&(row, col)) { '#' } else { '.' }).collect::<String>();
+            if row + 1 < self.height {
+                writeln!(f)?;
+            }
+            write!(f, "{line}")?;
+        }
+        Ok(())
+    }
+}
+
+#[allow(unused)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RulesError {
+    UnexpectedChar { line: usize, c: char },
+    EmptyShape { shape: usize },
+    NoShapes,
+    TooWide { shape: usize, width: usize },
+}
+
+impl Display for RulesError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RulesError::UnexpectedChar { line, c } => write!(f, "line {line}: unexpected {c:?}, shapes are drawn with '#' and '.'"),
+            RulesError::EmptyShape { shape } => write!(f, "shape {shape} has no rock cell"),
+            RulesError::NoShapes => write!(f, "there is no shape"),
+            RulesError::TooWide { shape, width } => write!(f, "shape {shape} does not fit in a {width} wide chamber once spawned"),
+        }
+    }
+}
+
+impl std::error::Error for RulesError {}
+
+// Shapes drawn like in the puzzle, top row first, separated by empty lines
+#[allow(unused)]
+pub fn parse_shapes(s: &str) -> Result<Vec<Shape>, RulesError> {
+    let mut shapes = vec![];
+    let mut drawing: Vec<&str> = vec![];
+    for (idx, line) in s.lines().chain([""]).enumerate() {
+        if !line.is_empty() {
+            if let Some(c) = line.chars().find(|c| *c != '#' && *c != '.') {
+                return Err(RulesError::UnexpectedChar { line: idx + 1, c });
+            }
+            drawing.push(line);
+            continue;
+        }
+        if drawing.is_empty() {
+            continue;
+        }
+        let height = drawing.len();
+        let cells = drawing.drain(..).enumerate()
+            .flat_map(|(row, line)| line.chars().enumerate()
+                .filter(|(_, c)| *c == '#')
+                .map(move |(col, _)| (height - 1 - row, col)))
+            .collect::<Vec<_>>();
+        if cells.is_empty() {
+            return Err(RulesError::EmptyShape { shape: shapes.len() });
+        }
+        let width = cells.iter().map(|(_, col)| col + 1).max().unwrap();
+        let height = cells.iter().map(|(row, _)| row + 1).max().unwrap();
+        // Empty rows below the rock are dropped
+        let bottom = cells.iter().map(|(row, _)| *row).min().unwrap();
+        let cells = cells.into_iter().map(|(row, col)| (row - bottom, col)).collect();
+        shapes.push(Shape { cells, width, height: height - bottom });
+    }
+    Ok(shapes)
+}
+
+// How the game is played : the rocks falling in turn, the chamber width and
+// where each rock appears, `spawn_left` columns from the left wall and
+// `spawn_above` empty rows above the highest rock or the floor
+#[allow(unused)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Rules {
+    shapes: Vec<Shape>,
+    width: usize,
+    spawn_left: usize,
+    spawn_above: usize,
+}
+
+#[allow(unused)]
+impl Rules {
+    pub fn new(shapes: Vec<Shape>, width: usize, spawn_left: usize, spawn_above: usize) -> Result<Self, RulesError> {
+        if shapes.is_empty() {
+            return Err(RulesError::NoShapes);
+        }
+        if let Some(shape) = shapes.iter().position(|shape| spawn_left + shape.width > width) {
+            return Err(RulesError::TooWide { shape, width });
+        }
+        Ok(Self { shapes, width, spawn_left, spawn_above })
+    }
+
+    pub fn parse(shapes: &str, width: usize, spawn_left: usize, spawn_above: usize) -> Result<Self, RulesError> {
+        Self::new(parse_shapes(shapes)?, width, spawn_left, spawn_above)
+    }
+
+    pub fn shapes(&self) -> &[Shape] {
+        &self.shapes
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn spawn_left(&self) -> usize {
+        self.spawn_left
+    }
+
+    pub fn spawn_above(&self) -> usize {
+        self.spawn_above
+    }
+
+    // Cells of the `rock`th rock when it appears above `top_y`
+    pub fn spawn(&self, rock: usize, top_y: usize) -> impl Iterator<Item=(usize, usize)> + '_ {
+        self.shapes[rock % self.shapes.len()].cells.iter()
+            .map(move |(row, col)| (top_y + self.spawn_above + row, self.spawn_left + col))
+    }
+}
+
+// The puzzle : five rocks in a 7 wide chamber, spawned 2 from the left and 3 above
+impl Default for Rules {
+    fn default() -> Self {
+        Self::parse(include_str!("rocks.txt"), 7, 2, 3).unwrap()
+    }
+}
+
+#[cfg(test)]
+#[allow(unused)]
+mod rules_tests {
+    #[allow(unused)]
+    use super::*;
+
+    #[test]
+    #[allow(unused)]
+    fn test_parse_shapes() {
+        let shapes = parse_shapes(include_str!("rocks.txt")).unwrap();
+        assert_eq!(5, shapes.len());
+        assert_eq!(vec![(0, 0), (0, 1), (0, 2), (0, 3)], shapes[0].cells);
+        assert_eq!(vec![(2, 2), (1, 2), (0, 0), (0, 1), (0, 2)], shapes[2].cells);
+        assert_eq!((1, 4), (shapes[3].width, shapes[3].height));
+        assert_eq!(include_str!("rocks.txt"), shapes.iter().map(|shape| shape.to_string()).collect::<Vec<_>>().join("\n\n"));
+
+        // Leading and trailing blank lines are ignored, as are empty rows under a rock
+        let shapes = parse_shapes("\n\n.#\n##\n..\n\n\n#\n").unwrap();
+        assert_eq!(vec![(1, 1), (0, 0), (0, 1)], shapes[0].cells);
+        assert_eq!((2, 2), (shapes[0].width, shapes[0].height));
+        assert_eq!(2, shapes.len());
+
+        assert_eq!(Err(RulesError::UnexpectedChar { line: 2, c: 'x' }), parse_shapes("#\n#x"));
+        assert_eq!(Err(RulesError::EmptyShape { shape: 1 }), parse_shapes("#\n\n..."));
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_rules() {
+        let rules = Rules::default();
+        assert_eq!(7, rules.width());
+        assert_eq!(vec![(15, 3), (14, 2), (14, 3), (14, 4), (13, 3)], rules.spawn(6, 10).collect::<Vec<_>>());
+        assert_eq!(Err(RulesError::TooWide { shape: 0, width: 5 }), Rules::parse(include_str!("rocks.txt"), 5, 2, 3));
+        assert_eq!(Err(RulesError::NoShapes), Rules::parse("", 7, 2, 3));
+        assert!(Rules::parse(include_str!("rocks.txt"), 4, 0, 3).is_ok());
+    }
+}
@@ -0,0 +1,204 @@
+use std::fmt::{Display, Formatter, Write};
+use std::str::FromStr;
+
+use crate::j17::{CellType, Jet, MAX_ROWS, parse_jets, Tetris};
+use crate::j17::rules::{parse_shapes, Rules, RulesError};
+
+#[allow(unused)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TetrisError {
+    UnexpectedLine { line: usize, expected: &'static str },
+    InvalidNumber { line: usize, number: String },
+    InvalidJet { line: usize, c: char },
+    InvalidShape { line: usize, error: RulesError },
+    InvalidRules(RulesError),
+    InvalidRow { line: usize },
+}
+
+impl Display for TetrisError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TetrisError::UnexpectedLine { line, expected } => write!(f, "line {line}: expected {expected}"),
+            TetrisError::InvalidNumber { line, number } => write!(f, "line {line}: invalid number {number}"),
+            TetrisError::InvalidJet { line, c } => write!(f, "line {line}: invalid jet {c:?}"),
+            TetrisError::InvalidShape { line, error } => write!(f, "line {line}: invalid shape, {error}"),
+            TetrisError::InvalidRules(error) => write!(f, "invalid rules, {error}"),
+            TetrisError::InvalidRow { line } => write!(f, "line {line}: invalid chamber row"),
+        }
+    }
+}
+
+impl std::error::Error for TetrisError {}
+
+struct Lines<'a> {
+    lines: std::iter::Peekable<std::iter::Enumerate<std::str::Lines<'a>>>,
+    // Lines read so far
+    read: usize,
+}
+
+impl<'a> Lines<'a> {
+    // Rest of the next line, which must start with `key`
+    fn field(&mut self, key: &str, expected: &'static str) -> Result<(usize, &'a str), TetrisError> {
+        let next = self.lines.next();
+        self.read += next.is_some() as usize;
+        match next {
+            Some((idx, line)) => line.strip_prefix(key).and_then(|rest| rest.strip_prefix(' '))
+                .map(|rest| (idx + 1, rest))
+                .ok_or(TetrisError::UnexpectedLine { line: idx + 1, expected }),
+            // The missing line is the one after the last
+            None => Err(TetrisError::UnexpectedLine { line: self.read + 1, expected }),
+        }
+    }
+
+    fn numbers<const N: usize>(&mut self, key: &str, expected: &'static str) -> Result<[usize; N], TetrisError> {
+        let (line, rest) = self.field(key, expected)?;
+        let numbers = rest.split(' ')
+            .map(|number| usize::from_str(number).map_err(|_| TetrisError::InvalidNumber { line, number: number.to_string() }))
+            .collect::<Result<Vec<_>, _>>()?;
+        numbers.try_into().map_err(|_| TetrisError::UnexpectedLine { line, expected })
+    }
+}
+
+// A game saved as text, one field per line :
+//
+// width 7
+// spawn 2 3
+// rocks 10
+// jet 57
+// dropped 0
+// jets >>><<><>...
+// shape ####
+// shape .#. ### .#.
+// ...
+// row |....#..|
+// ...
+//
+// shapes are drawn top row first with their rows separated by spaces, and
+// the chamber rows kept go from the top down
+#[allow(unused)]
+impl Tetris {
+    pub fn save(&self) -> String {
+        let mut saved = String::new();
+        writeln!(saved, "width {}", self.rules.width()).unwrap();
+        writeln!(saved, "spawn {} {}", self.rules.spawn_left(), self.rules.spawn_above()).unwrap();
+        writeln!(saved, "rocks {}", self.rocks).unwrap();
+        writeln!(saved, "jet {}", self.jet).unwrap();
+        writeln!(saved, "dropped {}", self.dropped_rows).unwrap();
+        let jets = self.jets.iter().map(|jet| match jet {
+            Jet::Left => '<',
+            Jet::Right => '>',
+        }).collect::<String>();
+        writeln!(saved, "jets {jets}").unwrap();
+        for shape in self.rules.shapes() {
+            writeln!(saved, "shape {}", shape.to_string().replace('\n', " ")).unwrap();
+        }
+        for row in self.chamber[..self.top_y].iter().rev() {
+            writeln!(saved, "row |{}|", row.iter().map(|cell| cell.to_string()).collect::<String>()).unwrap();
+        }
+        saved
+    }
+
+    pub fn load(s: &str) -> Result<Self, TetrisError> {
+        let mut lines = Lines { lines: s.lines().enumerate().peekable(), read: 0 };
+        let [width] = lines.numbers("width", "the chamber width")?;
+        let [spawn_left, spawn_above] = lines.numbers("spawn", "the spawn offsets")?;
+        let [rocks] = lines.numbers("rocks", "the number of rocks")?;
+        let [jet] = lines.numbers("jet", "the next jet")?;
+        let [dropped_rows] = lines.numbers("dropped", "the number of dropped rows")?;
+        let (line, jets) = lines.field("jets", "the jets")?;
+        let jets = parse_jets(jets).map_err(|c| TetrisError::InvalidJet { line, c })?;
+        if jet >= jets.len() {
+            return Err(TetrisError::InvalidNumber { line: 4, number: jet.to_string() });
+        }
+
+        let mut shapes = vec![];
+        while lines.lines.peek().is_some_and(|(_, line)| line.starts_with("shape ")) {
+            let (line, drawing) = lines.field("shape", "a shape")?;
+            let mut parsed = parse_shapes(&drawing.replace(' ', "\n")).map_err(|error| TetrisError::InvalidShape { line, error })?;
+            if parsed.len() != 1 {
+                return Err(TetrisError::UnexpectedLine { line, expected: "a single shape" });
+            }
+            shapes.append(&mut parsed);
+        }
+        let rules = Rules::new(shapes, width, spawn_left, spawn_above).map_err(TetrisError::InvalidRules)?;
+
+        let mut chamber = vec![];
+        while lines.lines.peek().is_some() {
+            let (line, row) = lines.field("row", "a chamber row")?;
+            let cells = row.strip_prefix('|').and_then(|row| row.strip_suffix('|'))
+                .filter(|cells| cells.len() == width)
+                .ok_or(TetrisError::InvalidRow { line })?;
+            let cells = cells.chars().map(|c| match c {
+                '.' => Ok(CellType::Empty),
+                '#' => Ok(CellType::Fixed),
+                _ => Err(TetrisError::InvalidRow { line }),
+            }).collect::<Result<Vec<_>, _>>()?;
+            chamber.push(cells);
+        }
+        chamber.reverse();
+        let top_y = chamber.len();
+        Ok(Tetris { rules, jets, chamber, dropped_rows, top_y, rocks, jet, drop_at: MAX_ROWS })
+    }
+}
+
+#[cfg(test)]
+#[allow(unused)]
+mod state_tests {
+    #[allow(unused)]
+    use super::*;
+
+    #[test]
+    #[allow(unused)]
+    fn test_save_and_load() {
+        let mut tetris = Tetris::new(include_str!("j17_test.txt"), Rules::default()).unwrap();
+        tetris.drop_rocks(3);
+        let saved = tetris.save();
+        assert!(saved.starts_with("width 7\nspawn 2 3\nrocks 3\n"));
+        assert!(saved.contains("\nshape .#. ### .#.\n"));
+        assert!(saved.ends_with("\nrow |..#....|\nrow |..#....|\nrow |####...|\nrow |..###..|\nrow |...#...|\nrow |..####.|\n"));
+
+        // Resumed games end up like the ones never stopped
+        let mut loaded = Tetris::load(&saved).unwrap();
+        assert_eq!(saved, loaded.save());
+        loaded.drop_rocks(2019);
+        assert_eq!(3068, loaded.height());
+
+        let mut tetris = Tetris::new(include_str!("j17_test.txt"), Rules::default()).unwrap();
+        tetris.drop_rocks(5000);
+        let mut loaded = Tetris::load(&tetris.save()).unwrap();
+        tetris.drop_rocks(1000);
+        loaded.drop_rocks(1000);
+        assert_eq!(tetris.height(), loaded.height());
+        assert_eq!(tetris.snapshot(40), loaded.snapshot(40));
+
+        // Other rules are saved too
+        let rules = Rules::parse("##\n.#\n\n#", 4, 1, 1).unwrap();
+        let mut tetris = Tetris::new("<><<", rules.clone()).unwrap();
+        tetris.drop_rocks(7);
+        let loaded = Tetris::load(&tetris.save()).unwrap();
+        assert_eq!(&rules, loaded.rules());
+        assert_eq!(tetris.height(), loaded.height());
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_load_errors() {
+        let saved = Tetris::new(">", Rules::default()).unwrap().save();
+        assert_eq!(Err(TetrisError::UnexpectedLine { line: 1, expected: "the chamber width" }),
+                   Tetris::load(&saved.replace("width", "wide")).map(|_| ()));
+        assert_eq!(Err(TetrisError::InvalidNumber { line: 2, number: "x".to_string() }),
+                   Tetris::load(&saved.replace("spawn 2 3", "spawn 2 x")).map(|_| ()));
+        assert_eq!(Err(TetrisError::InvalidJet { line: 6, c: '^' }),
+                   Tetris::load(&saved.replace("jets >", "jets >^")).map(|_| ()));
+        assert_eq!(Err(TetrisError::InvalidRules(RulesError::TooWide { shape: 0, width: 5 })),
+                   Tetris::load(&saved.replace("width 7", "width 5")).map(|_| ()));
+        assert_eq!(Err(TetrisError::InvalidRow { line: 12 }),
+                   Tetris::load(&format!("{saved}row |...#|\n")).map(|_| ()));
+        assert_eq!(Err(TetrisError::UnexpectedLine { line: 6, expected: "the jets" }),
+                   Tetris::load("width 7\nspawn 2 3\nrocks 0\njet 0\ndropped 0\n").map(|_| ()));
+
+        // New games are checked the same way
+        assert_eq!(Err(TetrisError::InvalidJet { line: 1, c: '^' }), Tetris::new("<>^\n", Rules::default()).map(|_| ()));
+        assert_eq!(Err(TetrisError::UnexpectedLine { line: 1, expected: "the jets" }), Tetris::new("\n", Rules::default()).map(|_| ()));
+    }
+}
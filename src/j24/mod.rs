@@ -1,5 +1,7 @@
 use smallvec::{SmallVec, smallvec};
-use vec_collections::{AbstractVecSet, VecSet};
+
+use crate::cycle::brent_by_key;
+use crate::search::bfs;
 
 struct Cell {
     north_b: bool,
@@ -22,171 +24,90 @@ impl Cell {
 }
 
 type Grid = SmallVec<[SmallVec<[Cell; 102]>; 37]>;
-type World = SmallVec<[SmallVec<[bool; 102]>; 37]>;
 
-#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
-struct Elt((u16, u8, u8));
+// (time modulo the blizzards period, row, col)
+type Node = (usize, usize, usize);
 
-struct ExploredNodes(VecSet<[Elt; 123927]>);
+struct Valley {
+    grid: Grid,
+    rows: usize,
+    cols: usize,
+    periodicity: usize,
+}
 
-impl ExploredNodes {
-    pub fn push(&mut self, elt: Elt) {
-        match self.0.insert(elt) {
-            true => {}
-            false => {}
+impl Valley {
+    fn parse(s: &str) -> Self {
+        let mut grid: Grid = smallvec!();
+        for line in s.lines() {
+            let mut row = smallvec!();
+            for c in line.chars() {
+                row.push(Cell::from_char(c));
+            }
+            grid.push(row)
         }
+        let rows = grid.len();
+        let cols = grid[0].len();
+        // Blizzards are back where they started every `rows - 2` steps
+        // vertically and every `cols - 2` steps horizontally. Free cells alone
+        // are not enough to tell two times apart, as crossing blizzards can
+        // leave the same cells free
+        let periodicity = brent_by_key(&0, |time| *time += 1, |time| (*time % (rows - 2), *time % (cols - 2))).length;
+        Valley { grid, rows, cols, periodicity }
     }
 
-    pub fn contains(&self, elt: &Elt) -> bool {
-        self.0.contains(elt)
+    fn entrance(&self) -> (usize, usize) {
+        (0, 1)
     }
-}
-
-fn breadth_first_search(grid: &Grid, world: &mut World, (start_row, start_col): (usize, usize), (end_row, end_col): (usize, usize),
-                        start_cost: usize) -> usize {
-    let rows = grid.len();
-    let cols = grid[0].len();
-
-    let periodicity = (rows - 2) * (cols - 2);
-
 
-    let mut nodes: SmallVec<[Elt; 1200]> = smallvec!(Elt((start_cost as u16, (start_row as u16).try_into().unwrap(), (start_col as u16).try_into().unwrap())));
-    let mut childs: SmallVec<[Elt; 1200]> = smallvec!();
-    let mut explored: ExploredNodes = ExploredNodes(VecSet::empty());
-    explored.push(nodes[0]);
-
-    let mut max_len = nodes.len();
-    while !nodes.is_empty() {
-        max_len = max_len.max(nodes.len());
-        let mut generated = false;
-        for elt in nodes.drain(..) {
-            let (cost, row, col) = elt.0;
-            if row == end_row as u8 && col == end_col as u8 {
-                return cost as usize;
-            }
-
-            if !generated {
-                generated = true;
-                let p = (cost + 1) % periodicity as u16;
-                for row in 0..rows {
-                    for col in 0..cols {
-                        world[row][col] = true;
-                    }
-                }
-                for (row, grid_row) in grid.iter().enumerate().take(rows) {
-                    for (col, cell) in grid_row.iter().enumerate().take(cols) {
-                        if cell.blocked {
-                            world[row][col] = false;
-                        }
-                        if cell.north_b {
-                            let p = p as isize;
-                            let mut row = row as isize;
-                            let rows = rows as isize;
-                            let new_p = p % (rows - 2);
-                            if new_p >= row {
-                                row = row + rows - 2
-                            }
-                            let (target_row, target_col) = (row - new_p, col);
-                            world[target_row as usize][target_col] = false;
-                        }
-                        if cell.south_b {
-                            let p = p as isize;
-                            let mut row = row as isize;
-                            let rows = rows as isize;
-                            let new_p = p % (rows - 2);
-                            if new_p + row >= rows - 1 {
-                                row = row - rows + 2
-                            }
-                            let (target_row, target_col) = (row + new_p, col);
-                            world[target_row as usize][target_col] = false;
-                        }
-                        if cell.east_b {
-                            let p = p as isize;
-                            let mut col = col as isize;
-                            let cols = cols as isize;
-                            let new_p = p % (cols - 2);
-                            if new_p + col >= cols - 1 {
-                                col = col - cols + 2
-                            }
-                            let (target_row, target_col) = (row, col + new_p);
-                            world[target_row][target_col as usize] = false;
-                        }
-                        if cell.west_b {
-                            let p = p as isize;
-                            let mut col = col as isize;
-                            let cols = cols as isize;
-                            let new_p = p % (cols - 2);
-                            if new_p >= col {
-                                col = col + cols - 2
-                            }
-                            let (target_row, target_col) = (row, col - new_p);
-                            world[target_row][target_col as usize] = false;
-                        }
-                    }
-                }
-            }
-
-            let new_p = (cost as usize + 1) % periodicity;
-
-            if world[u32::from(row) as usize][u32::from(col) as usize] &&
-                !explored.contains(&Elt((new_p as u16, row, col))) {
-                explored.push(Elt((new_p as u16, row, col)));
-                childs.push(Elt((cost + 1, row, col)));
-            }
-
-            if row > 0 && world[u32::from(row) as usize - 1][u32::from(col) as usize] &&
-                !explored.contains(&Elt((new_p as u16, row - 1, col))) {
-                explored.push(Elt((new_p as u16, row - 1, col)));
-                childs.push(Elt((cost + 1, row - 1, col)));
-            }
-
-            if row < rows as u8 - 1 && world[row as usize + 1][col as usize] &&
-                !explored.contains(&Elt((new_p as u16, row + 1, col))) {
-                explored.push(Elt((new_p as u16, row + 1, col)));
-                childs.push(Elt((cost + 1, row + 1, col)));
-            }
+    fn exit(&self) -> (usize, usize) {
+        (self.rows - 1, self.cols - 2)
+    }
 
-            if col > 0 && world[row as usize][col as usize - 1] &&
-                !explored.contains(&Elt((new_p as u16, row, col - 1))) {
-                explored.push(Elt((new_p as u16, row, col - 1)));
-                childs.push(Elt((cost + 1, row, col - 1)));
-            }
+    // Looks for the blizzards which would be on the cell at `time`, back
+    // where they started
+    fn is_free(&self, row: usize, col: usize, time: usize) -> bool {
+        if self.grid[row][col].blocked {
+            return false;
+        }
+        if row == 0 || row == self.rows - 1 {
+            return true;
+        }
+        let inner_rows = self.rows - 2;
+        let inner_cols = self.cols - 2;
+        let (r, c) = (row - 1, col - 1);
+        let (t_rows, t_cols) = (time % inner_rows, time % inner_cols);
+        !(self.grid[row][1 + (c + inner_cols - t_cols) % inner_cols].east_b ||
+            self.grid[row][1 + (c + t_cols) % inner_cols].west_b ||
+            self.grid[1 + (r + inner_rows - t_rows) % inner_rows][col].south_b ||
+            self.grid[1 + (r + t_rows) % inner_rows][col].north_b)
+    }
 
-            if col < cols as u8 - 1 && world[row as usize][col as usize + 1] &&
-                !explored.contains(&Elt((new_p as u16, row, col + 1))) {
-                explored.push(Elt((new_p as u16, row, col + 1)));
-                childs.push(Elt((cost + 1, row, col + 1)));
+    fn moves(&self, (phase, row, col): Node) -> SmallVec<[Node; 5]> {
+        let next = (phase + 1) % self.periodicity;
+        let mut moves = smallvec![];
+        for (d_row, d_col) in [(0, 0), (-1, 0), (1, 0), (0, -1), (0, 1)] {
+            let (Some(row), Some(col)) = (row.checked_add_signed(d_row), col.checked_add_signed(d_col)) else { continue };
+            if row < self.rows && col < self.cols && self.is_free(row, col, next) {
+                moves.push((next, row, col));
             }
         }
-        std::mem::swap(&mut nodes, &mut childs);
+        moves
     }
-    panic!();
+}
+
+fn breadth_first_search(valley: &Valley, (start_row, start_col): (usize, usize), end: (usize, usize),
+                        start_cost: usize) -> usize {
+    let search = bfs([(start_cost % valley.periodicity, start_row, start_col)],
+                     |node| valley.moves(*node),
+                     |(_, row, col)| (*row, *col) == end);
+    start_cost + search.cost().unwrap()
 }
 
 #[allow(unused)]
 pub fn _p1(s: &str) -> usize {
-    let (grid, mut world) = compute_grid_and_create_world_state(s);
-
-    breadth_first_search(&grid, &mut world, (0, 1), (grid.len() - 1, grid[0].len() - 2), 0)
-}
+    let valley = Valley::parse(s);
 
-fn compute_grid_and_create_world_state(s: &str) -> (Grid, World) {
-    let mut grid: Grid = smallvec!();
-    for line in s.lines() {
-        let mut row = smallvec!();
-        for c in line.chars() {
-            row.push(Cell::from_char(c));
-        }
-        grid.push(row)
-    }
-    let mut world: World = smallvec![];
-    for row in 0..grid.len() {
-        world.push(smallvec![]);
-        for _ in 0..grid[0].len() {
-            world[row].push(true)
-        }
-    }
-    (grid, world)
+    breadth_first_search(&valley, valley.entrance(), valley.exit(), 0)
 }
 
 #[allow(unused)]
@@ -196,11 +117,11 @@ pub fn p1() -> usize {
 
 #[allow(unused)]
 pub fn _p2(s: &str) -> usize {
-    let (grid, mut world) = compute_grid_and_create_world_state(s);
+    let valley = Valley::parse(s);
 
-    let first_way_cost = breadth_first_search(&grid, &mut world, (0, 1), (grid.len() - 1, grid[0].len() - 2), 0);
-    let second_way_cost = breadth_first_search(&grid, &mut world, (grid.len() - 1, grid[0].len() - 2), (0, 1), first_way_cost);
-    breadth_first_search(&grid, &mut world, (0, 1), (grid.len() - 1, grid[0].len() - 2), second_way_cost)
+    let first_way_cost = breadth_first_search(&valley, valley.entrance(), valley.exit(), 0);
+    let second_way_cost = breadth_first_search(&valley, valley.exit(), valley.entrance(), first_way_cost);
+    breadth_first_search(&valley, valley.entrance(), valley.exit(), second_way_cost)
 }
 
 #[allow(unused)]
@@ -227,4 +148,13 @@ mod j24_tests {
         assert_eq!(54, _p2(include_str!("j24_test.txt")));
         assert_eq!(785, _p2(include_str!("j24.txt")));
     }
+
+    #[test]
+    #[allow(unused)]
+    fn test_crossing_blizzards() {
+        // Blizzards crossing each other, the free cells repeat before they do
+        let valleys = ["#.######\n#>.<...#\n#......#\n######.#", "#.#####\n#.....#\n#>.<..#\n#####.#", "#.#####\n#>.<..#\n#####.#"];
+        assert_eq!(vec![6, 10, 5], valleys.iter().map(|s| Valley::parse(s).periodicity).collect::<Vec<_>>());
+        assert_eq!(vec![8, 7, 7], valleys.iter().map(|s| _p1(s)).collect::<Vec<_>>());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,311 @@
+use std::cmp::{max, min};
+use std::ops::RangeInclusive;
+
+pub trait Bound: Copy + Ord {
+    const MAX: Self;
+
+    fn succ(self) -> Self;
+    fn pred(self) -> Self;
+    fn span(start: Self, end: Self) -> u128;
+}
+
+macro_rules! impl_bound {
+    ($($t:ty),*) => {
+        $(
+            impl Bound for $t {
+                const MAX: Self = <$t>::MAX;
+
+                #[inline(always)]
+                fn succ(self) -> Self {
+                    self + 1
+                }
+
+                #[inline(always)]
+                fn pred(self) -> Self {
+                    self - 1
+                }
+
+                #[inline(always)]
+                fn span(start: Self, end: Self) -> u128 {
+                    (end as i128).abs_diff(start as i128) + 1
+                }
+            }
+        )*
+    };
+}
+
+impl_bound!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+impl Bound for u128 {
+    const MAX: Self = u128::MAX;
+
+    #[inline(always)]
+    fn succ(self) -> Self {
+        self + 1
+    }
+
+    #[inline(always)]
+    fn pred(self) -> Self {
+        self - 1
+    }
+
+    #[inline(always)]
+    fn span(start: Self, end: Self) -> u128 {
+        (end - start).saturating_add(1)
+    }
+}
+
+// Sorted, disjoint and non adjacent inclusive intervals
+#[allow(unused)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IntervalSet<T: Bound> {
+    intervals: Vec<RangeInclusive<T>>,
+}
+
+#[allow(unused)]
+impl<T: Bound> IntervalSet<T> {
+    pub fn new() -> Self {
+        Self { intervals: Vec::new() }
+    }
+
+    pub fn from_range(range: RangeInclusive<T>) -> Self {
+        let mut set = Self::new();
+        set.insert(range);
+        set
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=&RangeInclusive<T>> {
+        self.intervals.iter()
+    }
+
+    pub fn insert(&mut self, range: RangeInclusive<T>) {
+        let (mut start, mut end) = range.into_inner();
+        if start > end {
+            return;
+        }
+
+        // first interval which may touch the new one (its end + 1 >= start)
+        let first = self.intervals.partition_point(|r| *r.end() < start && r.end().succ() < start);
+        let mut last = first;
+        while last < self.intervals.len() {
+            let r = &self.intervals[last];
+            if *r.start() > end && (end == T::MAX || *r.start() > end.succ()) {
+                break;
+            }
+            start = min(start, *r.start());
+            end = max(end, *r.end());
+            last += 1;
+        }
+        self.intervals.splice(first..last, std::iter::once(start..=end));
+    }
+
+    pub fn contains(&self, value: T) -> bool {
+        let idx = self.intervals.partition_point(|r| *r.end() < value);
+        idx < self.intervals.len() && *self.intervals[idx].start() <= value
+    }
+
+    pub fn contains_range(&self, range: &RangeInclusive<T>) -> bool {
+        if range.is_empty() {
+            return true;
+        }
+        let idx = self.intervals.partition_point(|r| r.end() < range.start());
+        idx < self.intervals.len()
+            && self.intervals[idx].start() <= range.start()
+            && self.intervals[idx].end() >= range.end()
+    }
+
+    pub fn covered_len(&self) -> u128 {
+        self.intervals.iter()
+            .map(|r| T::span(*r.start(), *r.end()))
+            .fold(0u128, |acc, len| acc.saturating_add(len))
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        let mut result = self.clone();
+        for r in other.intervals.iter() {
+            result.insert(r.clone());
+        }
+        result
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self {
+        let mut intervals = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < self.intervals.len() && j < other.intervals.len() {
+            let a = &self.intervals[i];
+            let b = &other.intervals[j];
+            let start = max(*a.start(), *b.start());
+            let end = min(*a.end(), *b.end());
+            if start <= end {
+                intervals.push(start..=end);
+            }
+            if a.end() < b.end() {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        Self { intervals }
+    }
+
+    pub fn difference(&self, other: &Self) -> Self {
+        let mut intervals = Vec::new();
+        let mut j = 0;
+        for a in self.intervals.iter() {
+            let mut start = *a.start();
+            let end = *a.end();
+            let mut exhausted = false;
+            while j < other.intervals.len() && other.intervals[j].end() < &start {
+                j += 1;
+            }
+            let mut k = j;
+            while k < other.intervals.len() && *other.intervals[k].start() <= end {
+                let b = &other.intervals[k];
+                if *b.start() > start {
+                    intervals.push(start..=b.start().pred());
+                }
+                if *b.end() >= end {
+                    exhausted = true;
+                    break;
+                }
+                start = b.end().succ();
+                k += 1;
+            }
+            if !exhausted {
+                intervals.push(start..=end);
+            }
+        }
+        Self { intervals }
+    }
+}
+
+#[allow(unused)]
+impl<T: Bound> FromIterator<RangeInclusive<T>> for IntervalSet<T> {
+    fn from_iter<I: IntoIterator<Item=RangeInclusive<T>>>(iter: I) -> Self {
+        let mut set = Self::new();
+        for range in iter {
+            set.insert(range);
+        }
+        set
+    }
+}
+
+// Static interval tree : intervals sorted by start, laid out as an implicit
+// balanced binary tree where every node knows the maximum end of its subtree
+#[allow(unused)]
+#[derive(Clone, Debug)]
+pub struct IntervalTree<T: Bound> {
+    intervals: Vec<(RangeInclusive<T>, usize)>,
+    max_end: Vec<T>,
+}
+
+#[allow(unused)]
+impl<T: Bound> IntervalTree<T> {
+    pub fn new(ranges: impl IntoIterator<Item=RangeInclusive<T>>) -> Self {
+        let mut intervals = ranges.into_iter().enumerate()
+            .map(|(id, r)| (r, id))
+            .collect::<Vec<_>>();
+        intervals.sort_unstable_by_key(|(r, _)| (*r.start(), *r.end()));
+        let mut max_end = intervals.iter().map(|(r, _)| *r.end()).collect::<Vec<_>>();
+        if !intervals.is_empty() {
+            Self::build(&mut max_end, 0, intervals.len() - 1);
+        }
+        Self { intervals, max_end }
+    }
+
+    fn build(max_end: &mut [T], lo: usize, hi: usize) -> T {
+        let mid = (lo + hi) / 2;
+        let mut m = max_end[mid];
+        if lo < mid {
+            m = max(m, Self::build(max_end, lo, mid - 1));
+        }
+        if mid < hi {
+            m = max(m, Self::build(max_end, mid + 1, hi));
+        }
+        max_end[mid] = m;
+        m
+    }
+
+    pub fn len(&self) -> usize {
+        self.intervals.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+
+    // Ids (insertion order) of every interval overlapping the query
+    pub fn overlapping(&self, query: &RangeInclusive<T>) -> Vec<usize> {
+        let mut result = Vec::new();
+        if !self.intervals.is_empty() && !query.is_empty() {
+            self.collect(query, 0, self.intervals.len() - 1, &mut result);
+        }
+        result.sort_unstable();
+        result
+    }
+
+    fn collect(&self, query: &RangeInclusive<T>, lo: usize, hi: usize, result: &mut Vec<usize>) {
+        let mid = (lo + hi) / 2;
+        if self.max_end[mid] < *query.start() {
+            return;
+        }
+        if lo < mid {
+            self.collect(query, lo, mid - 1, result);
+        }
+        let (r, id) = &self.intervals[mid];
+        if r.start() > query.end() {
+            return;
+        }
+        if r.end() >= query.start() {
+            result.push(*id);
+        }
+        if mid < hi {
+            self.collect(query, mid + 1, hi, result);
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(unused)]
+mod interval_set_tests {
+    #[allow(unused)]
+    use super::*;
+
+    #[test]
+    #[allow(unused)]
+    fn test_set_operations() {
+        let a: IntervalSet<u64> = [1..=5, 10..=20, 6..=7].into_iter().collect();
+        assert_eq!(vec![1..=7, 10..=20], a.iter().cloned().collect::<Vec<_>>());
+        assert_eq!(18, a.covered_len());
+
+        let b: IntervalSet<u64> = [4..=12, 18..=30].into_iter().collect();
+        assert_eq!(IntervalSet::from_range(1..=30), a.union(&b));
+        assert_eq!(vec![4..=7, 10..=12, 18..=20], a.intersection(&b).iter().cloned().collect::<Vec<_>>());
+        assert_eq!(vec![1..=3, 13..=17], a.difference(&b).iter().cloned().collect::<Vec<_>>());
+        assert_eq!(vec![8..=9, 21..=30], b.difference(&a).iter().cloned().collect::<Vec<_>>());
+
+        assert!(a.contains(15));
+        assert!(!a.contains(8));
+        assert!(a.contains_range(&(11..=19)));
+        assert!(!a.contains_range(&(5..=11)));
+
+        let full = IntervalSet::from_range(u8::MIN..=u8::MAX);
+        assert_eq!(256, full.covered_len());
+        assert!(full.difference(&IntervalSet::from_range(0..=255)).is_empty());
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_tree_overlapping() {
+        let ranges = [1u64..=3, 5..=9, 2..=6, 10..=10, 7..=1_000_000_000_000];
+        let tree = IntervalTree::new(ranges.iter().cloned());
+        assert_eq!(vec![0, 1, 2], tree.overlapping(&(3..=5)));
+        assert_eq!(vec![3, 4], tree.overlapping(&(10..=10)));
+        assert_eq!(vec![4], tree.overlapping(&(11..=u64::MAX)));
+        assert!(tree.overlapping(&(0..=0)).is_empty());
+    }
+}
@@ -1,16 +1,20 @@
 use std::ops::RangeInclusive;
 use std::str::FromStr;
 
+use crate::j4::interval_set::{Bound, IntervalTree};
+
+pub mod interval_set;
+
 #[inline(always)]
-fn get_range(s: &str) -> RangeInclusive<u8> {
+fn get_range<T: Bound + FromStr>(s: &str) -> RangeInclusive<T> {
     let mut split = s.split('-');
-    let split_start = u8::from_str(split.next().unwrap()).unwrap();
-    let split_end = u8::from_str(split.next().unwrap()).unwrap();
+    let split_start = T::from_str(split.next().unwrap()).ok().unwrap();
+    let split_end = T::from_str(split.next().unwrap()).ok().unwrap();
     split_start..=split_end
 }
 
 #[inline(always)]
-fn get_ranges(s: &str) -> (RangeInclusive<u8>, RangeInclusive<u8>) {
+fn get_ranges<T: Bound + FromStr>(s: &str) -> (RangeInclusive<T>, RangeInclusive<T>) {
     let mut split = s.split(',');
     let pair1 = split.next().unwrap();
     let pair2 = split.next().unwrap();
@@ -18,11 +22,59 @@ fn get_ranges(s: &str) -> (RangeInclusive<u8>, RangeInclusive<u8>) {
     (get_range(pair1), get_range(pair2))
 }
 
+// Every assignment of the file, two per line, numbered in reading order
+#[allow(unused)]
+pub struct Assignments<T: Bound> {
+    ranges: Vec<RangeInclusive<T>>,
+    tree: IntervalTree<T>,
+}
+
+#[allow(unused)]
+impl<T: Bound + FromStr> Assignments<T> {
+    pub fn parse(s: &str) -> Self {
+        let ranges = s.lines()
+            .flat_map(|line| {
+                let (range1, range2) = get_ranges(line);
+                [range1, range2]
+            })
+            .collect::<Vec<_>>();
+        let tree = IntervalTree::new(ranges.iter().cloned());
+        Self { ranges, tree }
+    }
+
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    pub fn get(&self, id: usize) -> &RangeInclusive<T> {
+        &self.ranges[id]
+    }
+
+    pub fn overlapping(&self, id: usize) -> Vec<usize> {
+        let mut result = self.tree.overlapping(&self.ranges[id]);
+        result.retain(|other| *other != id);
+        result
+    }
+
+    pub fn containing(&self, id: usize) -> Vec<usize> {
+        let range = &self.ranges[id];
+        let mut result = self.tree.overlapping(range);
+        result.retain(|other| *other != id
+            && self.ranges[*other].start() <= range.start()
+            && self.ranges[*other].end() >= range.end());
+        result
+    }
+}
+
 #[allow(unused)]
 pub fn _p1(s: &str) -> usize {
     let mut total = 0u16;
     for line in s.lines() {
-        let (range1, range2) = get_ranges(line);
+        let (range1, range2) = get_ranges::<u64>(line);
 
         if range1.start() <= range2.start() && range1.end() >= range2.end() ||
             range2.start() <= range1.start() && range2.end() >= range1.end() {
@@ -41,9 +93,9 @@ pub fn p1() -> usize {
 pub fn _p2(s: &str) -> usize {
     let mut total = 0u16;
     for line in s.lines() {
-        let (range1, range2) = get_ranges(line);
+        let (range1, range2) = get_ranges::<u64>(line);
 
-        if range1.into_iter().any(|elt| range2.contains(&elt)) {
+        if range1.start() <= range2.end() && range2.start() <= range1.end() {
             total += 1
         }
     }
@@ -74,4 +126,17 @@ mod j4_tests {
         assert_eq!(4, _p2(include_str!("j4_test.txt")));
         assert_eq!(804, _p2(include_str!("j4.txt")));
     }
+
+    #[test]
+    #[allow(unused)]
+    fn test_assignments() {
+        let assignments = Assignments::<u64>::parse(include_str!("j4_test.txt"));
+        assert_eq!(12, assignments.len());
+        assert_eq!(vec![0, 4, 6, 7, 9, 10, 11], assignments.overlapping(3));
+        assert_eq!(vec![6, 7, 9, 10, 11], assignments.containing(3));
+
+        let wide = Assignments::<u64>::parse("1-5000000000,4999999999-6000000000\n300-400,1-2");
+        assert_eq!(vec![1, 2, 3], wide.overlapping(0));
+        assert_eq!(vec![0], wide.containing(2));
+    }
 }
\ No newline at end of file
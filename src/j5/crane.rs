@@ -0,0 +1,201 @@
+use std::fmt::{Display, Formatter};
+
+use smallvec::SmallVec;
+
+pub type Stack = SmallVec<[char; 128]>;
+
+#[allow(unused)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Move {
+    pub times: usize,
+    pub from: usize,
+    pub to: usize,
+}
+
+#[allow(unused)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CraneError {
+    NoSuchStack(usize),
+    EmptyStack { stack: usize, requested: usize, available: usize },
+}
+
+impl Display for CraneError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CraneError::NoSuchStack(stack) => write!(f, "there is no stack {}", stack + 1),
+            CraneError::EmptyStack { stack, requested, available } =>
+                write!(f, "cannot take {requested} crates from stack {} which only holds {available}", stack + 1),
+        }
+    }
+}
+
+impl std::error::Error for CraneError {}
+
+// Moves crates between stacks, the move has already been checked against
+// the stacks bounds and sizes when `apply` is called
+pub trait Crane {
+    fn apply(&self, stacks: &mut [Stack], mv: &Move);
+}
+
+// One crate at a time, reverses the moved crates
+#[derive(Copy, Clone, Debug, Default)]
+pub struct CrateMover9000;
+
+// Every crate at once, keeps the moved crates order
+#[derive(Copy, Clone, Debug, Default)]
+pub struct CrateMover9001;
+
+// At most `capacity` crates at once, each grab keeps its crates order
+#[allow(unused)]
+#[derive(Copy, Clone, Debug)]
+pub struct CapacityLimitedCrane(pub usize);
+
+#[inline(always)]
+fn move_chunk(stacks: &mut [Stack], from: usize, to: usize, count: usize) {
+    let start = stacks[from].len() - count;
+    let chunk: SmallVec<[char; 128]> = stacks[from].drain(start..).collect();
+    stacks[to].extend(chunk);
+}
+
+impl Crane for CrateMover9000 {
+    fn apply(&self, stacks: &mut [Stack], mv: &Move) {
+        for _ in 0..mv.times {
+            let popped = stacks[mv.from].pop().unwrap();
+            stacks[mv.to].push(popped);
+        }
+    }
+}
+
+impl Crane for CrateMover9001 {
+    fn apply(&self, stacks: &mut [Stack], mv: &Move) {
+        move_chunk(stacks, mv.from, mv.to, mv.times);
+    }
+}
+
+impl Crane for CapacityLimitedCrane {
+    fn apply(&self, stacks: &mut [Stack], mv: &Move) {
+        assert!(self.0 > 0, "a crane must be able to lift at least one crate");
+        let mut remaining = mv.times;
+        while remaining > 0 {
+            let count = remaining.min(self.0);
+            move_chunk(stacks, mv.from, mv.to, count);
+            remaining -= count;
+        }
+    }
+}
+
+// What is needed to revert a move whatever the crane used
+#[allow(unused)]
+#[derive(Clone, Debug)]
+pub struct AppliedMove {
+    pub mv: Move,
+    from_len: usize,
+    to_len: usize,
+    taken: Stack,
+}
+
+#[allow(unused)]
+pub struct Simulation<C: Crane> {
+    crane: C,
+    stacks: Vec<Stack>,
+    moves: Vec<Move>,
+    history: Vec<AppliedMove>,
+}
+
+#[allow(unused)]
+impl<C: Crane> Simulation<C> {
+    pub fn new(crane: C, stacks: Vec<Stack>, moves: Vec<Move>) -> Self {
+        Self {
+            crane,
+            stacks,
+            moves,
+            history: Vec::new(),
+        }
+    }
+
+    pub fn stacks(&self) -> &[Stack] {
+        &self.stacks
+    }
+
+    pub fn moves(&self) -> &[Move] {
+        &self.moves
+    }
+
+    pub fn history(&self) -> &[AppliedMove] {
+        &self.history
+    }
+
+    // Number of moves applied so far
+    pub fn position(&self) -> usize {
+        self.history.len()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.history.len() == self.moves.len()
+    }
+
+    // Applies the next move, returns it or None when every move has been done
+    pub fn step(&mut self) -> Result<Option<Move>, CraneError> {
+        let mv = match self.moves.get(self.history.len()) {
+            None => return Ok(None),
+            Some(mv) => *mv,
+        };
+        for stack in [mv.from, mv.to] {
+            if stack >= self.stacks.len() {
+                return Err(CraneError::NoSuchStack(stack));
+            }
+        }
+        let available = self.stacks[mv.from].len();
+        if available < mv.times {
+            return Err(CraneError::EmptyStack { stack: mv.from, requested: mv.times, available });
+        }
+
+        let applied = AppliedMove {
+            mv,
+            from_len: available,
+            to_len: self.stacks[mv.to].len(),
+            taken: self.stacks[mv.from][(available - mv.times)..].iter().copied().collect(),
+        };
+        self.crane.apply(&mut self.stacks, &mv);
+        self.history.push(applied);
+        Ok(Some(mv))
+    }
+
+    // Reverts the last applied move, returns it or None when at the start
+    pub fn undo(&mut self) -> Option<Move> {
+        let applied = self.history.pop()?;
+        let mv = applied.mv;
+        if mv.from != mv.to {
+            self.stacks[mv.to].truncate(applied.to_len);
+        }
+        let from = &mut self.stacks[mv.from];
+        from.truncate(applied.from_len - mv.times);
+        from.extend(applied.taken);
+        Some(mv)
+    }
+
+    pub fn run(&mut self) -> Result<(), CraneError> {
+        while self.step()?.is_some() {}
+        Ok(())
+    }
+
+    pub fn rewind(&mut self) {
+        while self.undo().is_some() {}
+    }
+
+    // Undoes or replays moves until exactly `position` moves are applied
+    pub fn seek(&mut self, position: usize) -> Result<(), CraneError> {
+        let position = position.min(self.moves.len());
+        while self.history.len() > position {
+            self.undo();
+        }
+        while self.history.len() < position {
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    pub fn tops(&self) -> impl Iterator<Item=Option<char>> + '_ {
+        self.stacks.iter().map(|stack| stack.last().copied())
+    }
+}
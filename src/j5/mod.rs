@@ -3,6 +3,10 @@ use std::str::FromStr;
 
 use smallvec::{smallvec, SmallVec};
 
+use crate::j5::crane::{Crane, CrateMover9000, CrateMover9001, Move, Simulation, Stack};
+
+pub mod crane;
+
 pub struct Chars(SmallVec<[char; 128]>);
 
 impl Display for Chars {
@@ -14,29 +18,26 @@ impl Display for Chars {
     }
 }
 
-fn get_stacks(s: &str, keep_order: bool) -> Chars {
-    let mut stack_of_stacks: SmallVec<[SmallVec<[char; 128]>; 128]> = smallvec!();
+fn parse_stacks(s: &str) -> (Vec<Stack>, usize) {
+    let mut stack_of_stacks: Vec<Stack> = Vec::new();
     let mut stack_lines: SmallVec<[&str; 128]> = smallvec!();
-    let mut max_length = 0;
 
     for l in s.lines() {
         if l.is_empty() {
             break;
         }
-        if l.len() > max_length {
-            max_length = l.len();
-        }
         stack_lines.push(l);
     }
 
-    let num_stacks = (max_length + 1) / 4;
+    // the footer numbers every stack, even the ones which are empty
+    let num_stacks = stack_lines.last().map_or(0, |footer| footer.split_whitespace().count());
 
     for _ in 0..num_stacks {
         stack_of_stacks.push(smallvec!())
     }
 
-    for (_, stack_line) in stack_lines.iter().rev().skip(1).enumerate() {
-        for col in 0..num_stacks {
+    for stack_line in stack_lines.iter().rev().skip(1) {
+        for (col, stack) in stack_of_stacks.iter_mut().enumerate() {
             let cell = (col * 4) + 1;
             if cell >= stack_line.len() {
                 continue;
@@ -45,11 +46,59 @@ fn get_stacks(s: &str, keep_order: bool) -> Chars {
             if c == ' ' {
                 continue;
             }
-            stack_of_stacks[col].push(stack_line.chars().nth(cell).unwrap());
+            stack.push(c);
         }
     }
 
-    for line in s.lines().skip(stack_lines.len() + 1) {
+    (stack_of_stacks, stack_lines.len())
+}
+
+// Same layout as the puzzle input : one `[X]` cell per stack separated by a
+// space, trailing spaces trimmed and a footer with the stack numbers
+#[allow(unused)]
+pub fn render(stacks: &[Stack]) -> String {
+    let height = stacks.iter().map(|stack| stack.len()).max().unwrap_or(0);
+    let mut drawing = String::new();
+    let mut line = String::new();
+
+    for level in (0..height).rev() {
+        line.clear();
+        for (col, stack) in stacks.iter().enumerate() {
+            if col > 0 {
+                line.push(' ');
+            }
+            match stack.get(level) {
+                Some(c) => write!(line, "[{c}]").unwrap(),
+                None => line.push_str("   "),
+            }
+        }
+        drawing.push_str(line.trim_end());
+        drawing.push('\n');
+    }
+
+    line.clear();
+    for col in 0..stacks.len() {
+        write!(line, "{:<4}", format!(" {}", col + 1)).unwrap();
+    }
+    drawing.push_str(line.trim_end());
+    drawing.push('\n');
+    drawing
+}
+
+#[allow(unused)]
+pub fn dump_drawings<C: Crane, W: std::io::Write>(s: &str, crane: C, out: &mut W) -> Result<(), Box<dyn std::error::Error>> {
+    let mut simulation = build_simulation(s, crane);
+    writeln!(out, "{}", render(simulation.stacks()))?;
+    while let Some(mv) = simulation.step()? {
+        writeln!(out, "move {} from {} to {}", mv.times, mv.from + 1, mv.to + 1)?;
+        writeln!(out, "{}", render(simulation.stacks()))?;
+    }
+    Ok(())
+}
+
+fn parse_moves(s: &str, skip: usize) -> Vec<Move> {
+    let mut moves = Vec::new();
+    for line in s.lines().skip(skip + 1) {
         let mut words = line.split(' ');
         words.next().unwrap();
         let times = usize::from_str(words.next().unwrap()).unwrap();
@@ -57,27 +106,30 @@ fn get_stacks(s: &str, keep_order: bool) -> Chars {
         let from = usize::from_str(words.next().unwrap()).unwrap() - 1;
         words.next().unwrap();
         let to = usize::from_str(words.next().unwrap()).unwrap() - 1;
-
-        if !keep_order {
-            for _ in 0..times {
-                let popped = stack_of_stacks[from].pop().unwrap();
-                stack_of_stacks[to].push(popped);
-            }
-        } else {
-            let mut temp_vec: SmallVec<[char; 128]> = smallvec!();
-            for _ in 0..times {
-                let popped = stack_of_stacks[from].pop().unwrap();
-                temp_vec.push(popped);
-            }
-            while !temp_vec.is_empty() {
-                stack_of_stacks[to].push(temp_vec.pop().unwrap());
-            }
-        }
+        moves.push(Move { times, from, to });
     }
+    moves
+}
+
+#[allow(unused)]
+pub fn parse(s: &str) -> (Vec<Stack>, Vec<Move>) {
+    let (stacks, stack_lines) = parse_stacks(s);
+    (stacks, parse_moves(s, stack_lines))
+}
+
+#[allow(unused)]
+pub fn build_simulation<C: Crane>(s: &str, crane: C) -> Simulation<C> {
+    let (stacks, moves) = parse(s);
+    Simulation::new(crane, stacks, moves)
+}
+
+fn get_tops<C: Crane>(s: &str, crane: C) -> Chars {
+    let mut simulation = build_simulation(s, crane);
+    simulation.run().unwrap();
 
     let mut buffer = Chars(smallvec![]);
-    for mut stack in stack_of_stacks {
-        buffer.0.push(stack.pop().unwrap())
+    for top in simulation.tops() {
+        buffer.0.push(top.unwrap_or(' '))
     }
 
     buffer
@@ -85,7 +137,7 @@ fn get_stacks(s: &str, keep_order: bool) -> Chars {
 
 #[allow(unused)]
 pub fn _p1(s: &'static str) -> Chars {
-    get_stacks(s, false)
+    get_tops(s, CrateMover9000)
 }
 
 #[allow(unused)]
@@ -95,7 +147,7 @@ pub fn p1() -> Chars {
 
 #[allow(unused)]
 pub fn _p2(s: &'static str) -> Chars {
-    get_stacks(s, true)
+    get_tops(s, CrateMover9001)
 }
 
 #[allow(unused)]
@@ -124,4 +176,84 @@ mod j5_tests {
         assert_eq!("MCD", _p2(include_str!("j5_test.txt")).0.into_iter().join(""));
         assert_eq!("TDGJQTZSL", _p2(include_str!("j5.txt")).0.into_iter().join(""));
     }
+
+    #[test]
+    #[allow(unused)]
+    fn test_render_round_trip() {
+        for input in [include_str!("j5_test.txt"), include_str!("j5.txt")] {
+            let drawing = input.split("\n\n").next().unwrap();
+            let (stacks, _) = parse(input);
+            assert_eq!(format!("{drawing}\n"), render(&stacks));
+
+            let mut simulation = build_simulation(input, CrateMover9001);
+            simulation.run().unwrap();
+            let (parsed, _) = parse(&render(simulation.stacks()));
+            assert_eq!(simulation.stacks(), parsed);
+        }
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_render_many_stacks() {
+        let mut stacks: Vec<Stack> = (0..12).map(|_| smallvec![]).collect();
+        stacks[0].push('A');
+        stacks[10].extend(['B', 'C']);
+        let drawing = render(&stacks);
+        assert_eq!(concat!(
+            "                                        [C]\n",
+            "[A]                                     [B]\n",
+            " 1   2   3   4   5   6   7   8   9   10  11  12\n"), drawing);
+        assert_eq!(stacks, parse(&drawing).0);
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_dump_drawings() {
+        let mut out = Vec::new();
+        dump_drawings(include_str!("j5_test.txt"), CrateMover9000, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("    [D]\n[N] [C]\n[Z] [M] [P]\n 1   2   3\n\nmove 1 from 2 to 1\n[D]\n[N] [C]\n"));
+        assert!(out.ends_with("move 1 from 1 to 2\n        [Z]\n        [N]\n        [D]\n[C] [M] [P]\n 1   2   3\n\n"));
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_simulation_undo() {
+        let mut simulation = build_simulation(include_str!("j5.txt"), CrateMover9001);
+        let initial = simulation.stacks().to_vec();
+        simulation.run().unwrap();
+        let done = simulation.stacks().to_vec();
+        assert!(simulation.is_finished());
+
+        simulation.seek(10).unwrap();
+        assert_eq!(10, simulation.position());
+        simulation.rewind();
+        assert_eq!(initial, simulation.stacks());
+        simulation.run().unwrap();
+        assert_eq!(done, simulation.stacks());
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_simulation_cranes() {
+        let mut simulation = build_simulation(include_str!("j5_test.txt"), crane::CapacityLimitedCrane(1));
+        simulation.run().unwrap();
+        assert_eq!("CMZ", simulation.tops().flatten().join(""));
+
+        let mut simulation = build_simulation(include_str!("j5_test.txt"), crane::CapacityLimitedCrane(2));
+        assert_eq!(Some(Move { times: 1, from: 1, to: 0 }), simulation.step().unwrap());
+        assert_eq!(Some(Move { times: 3, from: 0, to: 2 }), simulation.step().unwrap());
+        assert_eq!(vec!['P', 'N', 'D', 'Z'], simulation.stacks()[2].to_vec());
+        assert_eq!(Some(Move { times: 3, from: 0, to: 2 }), simulation.undo());
+        assert_eq!(vec!['Z', 'N', 'D'], simulation.stacks()[0].to_vec());
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_simulation_empty_stack() {
+        let (stacks, _) = parse(include_str!("j5_test.txt"));
+        let mut broken = Simulation::new(CrateMover9000, stacks, vec![Move { times: 4, from: 0, to: 1 }]);
+        assert_eq!(Err(crane::CraneError::EmptyStack { stack: 0, requested: 4, available: 2 }), broken.step());
+        assert_eq!(0, broken.position());
+    }
 }
\ No newline at end of file
@@ -1,3 +1,6 @@
+use std::collections::{HashMap, VecDeque};
+use std::io::{Error, ErrorKind, Read};
+
 fn get_start_position<const MIN_CHARS: usize>(s: &str) -> usize {
     let mut last_chars = ['$'; MIN_CHARS];
     'outer: for (i, c) in s.lines().next().unwrap().chars().enumerate() {
@@ -16,6 +19,130 @@ fn get_start_position<const MIN_CHARS: usize>(s: &str) -> usize {
     panic!("{}", format!("there were no unique {MIN_CHARS} characters following each other"));
 }
 
+pub const DEFAULT_BLOCK_SIZE: usize = 4096;
+
+// Finds every position where the last `window` chars are all different,
+// reading the input by blocks so it can run over unbounded streams
+#[allow(unused)]
+pub struct MarkerScanner<R: Read> {
+    reader: R,
+    window: usize,
+    block: Vec<u8>,
+    undecoded: Vec<u8>,
+    last_chars: VecDeque<char>,
+    counts: HashMap<char, usize>,
+    duplicates: usize,
+    position: usize,
+    found: VecDeque<usize>,
+    eof: bool,
+}
+
+#[allow(unused)]
+impl<R: Read> MarkerScanner<R> {
+    pub fn new(reader: R, window: usize) -> Self {
+        Self::with_block_size(reader, window, DEFAULT_BLOCK_SIZE)
+    }
+
+    pub fn with_block_size(reader: R, window: usize, block_size: usize) -> Self {
+        assert!(window > 0, "marker window must not be empty");
+        assert!(block_size > 0, "block size must not be empty");
+        Self {
+            reader,
+            window,
+            block: vec![0u8; block_size],
+            undecoded: Vec::new(),
+            last_chars: VecDeque::with_capacity(window + 1),
+            counts: HashMap::new(),
+            duplicates: 0,
+            position: 0,
+            found: VecDeque::new(),
+            eof: false,
+        }
+    }
+
+    pub fn first(&mut self) -> std::io::Result<Option<usize>> {
+        self.next().transpose()
+    }
+
+    pub fn first_n(&mut self, n: usize) -> std::io::Result<Vec<usize>> {
+        self.take(n).collect()
+    }
+
+    pub fn all(&mut self) -> std::io::Result<Vec<usize>> {
+        self.collect()
+    }
+
+    fn push_char(&mut self, c: char) {
+        self.position += 1;
+        self.last_chars.push_back(c);
+        let count = self.counts.entry(c).or_insert(0);
+        *count += 1;
+        if *count == 2 {
+            self.duplicates += 1;
+        }
+
+        if self.last_chars.len() > self.window {
+            let removed = self.last_chars.pop_front().unwrap();
+            let count = self.counts.get_mut(&removed).unwrap();
+            *count -= 1;
+            match *count {
+                0 => { self.counts.remove(&removed); }
+                1 => self.duplicates -= 1,
+                _ => {}
+            }
+        }
+
+        if self.last_chars.len() == self.window && self.duplicates == 0 {
+            self.found.push_back(self.position);
+        }
+    }
+
+    fn read_block(&mut self) -> std::io::Result<()> {
+        let read = loop {
+            match self.reader.read(&mut self.block) {
+                Ok(read) => break read,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        };
+        if read == 0 {
+            self.eof = true;
+            if !self.undecoded.is_empty() {
+                return Err(Error::new(ErrorKind::InvalidData, "stream ends in the middle of an utf-8 char"));
+            }
+            return Ok(());
+        }
+
+        let mut bytes = std::mem::take(&mut self.undecoded);
+        bytes.extend_from_slice(&self.block[..read]);
+        let valid_up_to = match std::str::from_utf8(&bytes) {
+            Ok(_) => bytes.len(),
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(e) => return Err(Error::new(ErrorKind::InvalidData, e)),
+        };
+        let (valid, rest) = bytes.split_at(valid_up_to);
+        for c in std::str::from_utf8(valid).unwrap().chars() {
+            self.push_char(c);
+        }
+        self.undecoded = rest.to_vec();
+        Ok(())
+    }
+}
+
+impl<R: Read> Iterator for MarkerScanner<R> {
+    type Item = std::io::Result<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.found.is_empty() && !self.eof {
+            if let Err(e) = self.read_block() {
+                self.eof = true;
+                return Some(Err(e));
+            }
+        }
+        self.found.pop_front().map(Ok)
+    }
+}
+
 #[allow(unused)]
 pub fn _p1(s: &str) -> usize {
     get_start_position::<4>(s)
@@ -55,4 +182,31 @@ mod j6_tests {
         assert_eq!(19, _p2(include_str!("j6_test.txt")));
         assert_eq!(3298, _p2(include_str!("j6.txt")));
     }
+
+    #[test]
+    #[allow(unused)]
+    fn test_marker_scanner() {
+        let input = include_str!("j6.txt");
+        assert_eq!(Some(1929), MarkerScanner::new(input.as_bytes(), 4).first().unwrap());
+        assert_eq!(Some(3298), MarkerScanner::with_block_size(input.as_bytes(), 14, 7).first().unwrap());
+
+        let mut scanner = MarkerScanner::with_block_size("aabcbdd".as_bytes(), 3, 2);
+        assert_eq!(vec![4, 6], scanner.all().unwrap());
+        let mut scanner = MarkerScanner::new("abcdefgh".as_bytes(), 4);
+        assert_eq!(vec![4, 5, 6], scanner.first_n(3).unwrap());
+        assert_eq!(vec![7, 8], scanner.all().unwrap());
+        assert_eq!(None, MarkerScanner::new("aaaa".as_bytes(), 2).first().unwrap());
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_marker_scanner_utf8() {
+        let input = "ééàéèà→é";
+        let mut scanner = MarkerScanner::with_block_size(input.as_bytes(), 3, 1);
+        assert_eq!(vec![5, 6, 7, 8], scanner.all().unwrap());
+
+        let truncated = &input.as_bytes()[..3];
+        let mut scanner = MarkerScanner::new(truncated, 2);
+        assert!(scanner.all().is_err());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,122 @@
+use std::fmt::{Display, Formatter, Write};
+
+use crate::j7::vfs::{Inconsistency, Node, NodeId, ROOT, VirtualFs};
+
+#[allow(unused)]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct InconsistentLog(pub Vec<Inconsistency>);
+
+impl Display for InconsistentLog {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "the terminal log contradicts itself:")?;
+        for inconsistency in self.0.iter() {
+            writeln!(f, "  {inconsistency}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for InconsistentLog {}
+
+// Rebuilds the filesystem and refuses logs which contradict themselves
+#[allow(unused)]
+pub fn replay(s: &str) -> Result<VirtualFs<'_>, InconsistentLog> {
+    let fs = VirtualFs::parse(s);
+    if fs.inconsistencies().is_empty() {
+        Ok(fs)
+    } else {
+        Err(InconsistentLog(fs.inconsistencies().to_vec()))
+    }
+}
+
+fn sorted_children(fs: &VirtualFs, id: NodeId) -> Vec<NodeId> {
+    let mut children = fs.children(id).to_vec();
+    children.sort_by_key(|child| fs.node(*child).name());
+    children
+}
+
+// Same listing as the puzzle statement, entries sorted by name
+#[allow(unused)]
+pub fn tree_listing(fs: &VirtualFs) -> String {
+    let mut listing = String::new();
+    tree_listing_rec(fs, ROOT, 0, &mut listing);
+    listing
+}
+
+fn tree_listing_rec(fs: &VirtualFs, id: NodeId, depth: usize, listing: &mut String) {
+    let indent = "  ".repeat(depth);
+    match fs.node(id) {
+        Node::Dir { name, .. } => {
+            writeln!(listing, "{indent}- {name} (dir)").unwrap();
+            for child in sorted_children(fs, id) {
+                tree_listing_rec(fs, child, depth + 1, listing);
+            }
+        }
+        Node::File { name, size, .. } => {
+            writeln!(listing, "{indent}- {name} (file, size={size})").unwrap();
+        }
+    }
+}
+
+fn push_json_string(json: &mut String, s: &str) {
+    json.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(json, "\\u{:04x}", c as u32).unwrap(),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+// {"name": .., "type": "dir"|"file", "size": .., "children": [..]}, the
+// size of a directory is its total size
+#[allow(unused)]
+pub fn to_json(fs: &VirtualFs) -> String {
+    let mut json = String::new();
+    to_json_rec(fs, ROOT, &mut json);
+    json
+}
+
+fn to_json_rec(fs: &VirtualFs, id: NodeId, json: &mut String) {
+    let node = fs.node(id);
+    json.push_str("{\"name\":");
+    push_json_string(json, node.name());
+    if node.is_dir() {
+        write!(json, ",\"type\":\"dir\",\"size\":{},\"children\":[", fs.size(id)).unwrap();
+        for (i, child) in sorted_children(fs, id).into_iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            to_json_rec(fs, child, json);
+        }
+        json.push_str("]}");
+    } else {
+        write!(json, ",\"type\":\"file\",\"size\":{}}}", fs.size(id)).unwrap();
+    }
+}
+
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+// POSIX shell script recreating the layout with sparse files, in the
+// directory given as first argument or in a new temporary one
+#[allow(unused)]
+pub fn to_shell_script(fs: &VirtualFs) -> String {
+    let mut script = String::new();
+    script.push_str("#!/bin/sh\nset -eu\nroot=\"${1:-$(mktemp -d)}\"\nmkdir -p \"$root\"\n");
+    for id in fs.walk(ROOT).into_iter().skip(1) {
+        let path = shell_quote(&fs.path(id)[1..]);
+        match fs.node(id) {
+            Node::Dir { .. } => writeln!(script, "mkdir -p \"$root\"/{path}").unwrap(),
+            Node::File { size, .. } => writeln!(script, "truncate -s {size} \"$root\"/{path}").unwrap(),
+        }
+    }
+    script.push_str("echo \"$root\"\n");
+    script
+}
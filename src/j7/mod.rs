@@ -1,170 +1,17 @@
-use std::str::FromStr;
+use crate::j7::vfs::{NodeKind, ROOT, SizeFilter, VirtualFs};
 
-use smallvec::{SmallVec, smallvec};
+pub mod export;
+pub mod vfs;
 
-const ESTIMATED_NODES_COUNT: usize = 512;
-const ESTIMATED_CHILDS_COUNT: usize = 16;
-const ESTIMATED_PATH_DEPTH_COUNT: usize = 16;
-
-#[derive(Debug, Eq, PartialEq, Clone)]
-enum Element {
-    Dir { name: &'static str, elements: SmallVec<[usize; ESTIMATED_CHILDS_COUNT]> },
-    File { name: &'static str, size: usize },
-}
-
-#[inline(always)]
-fn get_element_at_path(nodes: &SmallVec<[Element; ESTIMATED_NODES_COUNT]>, root: usize, path: &SmallVec<[&str; ESTIMATED_PATH_DEPTH_COUNT]>) -> usize {
-    let mut current = root;
-    for p in path {
-        current = *(match &nodes[current] {
-            Element::Dir { elements, .. } => {
-                elements.iter().find(|elt| match nodes[**elt] {
-                    Element::Dir { name, .. } => { name == *p }
-                    _ => { false }
-                }).unwrap()
-            }
-            Element::File { .. } => { panic!() }
-        })
-    }
-    current
-}
-
-#[inline(always)]
-fn compute_dir_of_size_at_most_than_max_size_and_return_total_dir_size(nodes: &SmallVec<[Element; ESTIMATED_NODES_COUNT]>, root: usize, max_size: usize, total: &mut usize) -> usize {
-    let mut self_size = 0;
-    match &nodes[root] {
-        Element::Dir { elements, .. } => {
-            for elt in elements.iter() {
-                self_size += compute_dir_of_size_at_most_than_max_size_and_return_total_dir_size(nodes, *elt, max_size, total);
-            }
-            if self_size <= max_size {
-                *total += self_size;
-            }
-        }
-        Element::File { size, .. } => {
-            self_size = *size;
-        }
-    }
-    self_size
-}
-
-#[inline(always)]
-fn find_smallest_dir(nodes: &SmallVec<[Element; ESTIMATED_NODES_COUNT]>, root: usize, min_size: usize, smallest_large_enough: &mut usize) -> usize {
-    let mut self_size = 0;
-    match &nodes[root] {
-        Element::Dir { elements, .. } => {
-            for elt in elements.iter() {
-                self_size += find_smallest_dir(nodes, *elt, min_size, smallest_large_enough);
-            }
-            if self_size >= min_size && self_size < *smallest_large_enough {
-                *smallest_large_enough = self_size;
-            }
-        }
-        Element::File { size, .. } => {
-            self_size = *size;
-        }
-    }
-    self_size
-}
-
-
-fn build_file_tree(s: &'static str) -> SmallVec<[Element; ESTIMATED_NODES_COUNT]> {
-    let mut nodes: SmallVec<[Element; ESTIMATED_NODES_COUNT]> = smallvec![];
-    let root = Element::Dir { name: "/", elements: smallvec![] };
-    nodes.push(root);
-    {
-        let mut current = 0usize;
-
-        let mut path: SmallVec<[&str; ESTIMATED_PATH_DEPTH_COUNT]> = smallvec![];
-
-        for line in s.lines().skip(1) {
-            if line.starts_with("ls") {
-                continue;
-            }
-            let mut split = line.split(' ');
-
-            match split.next().unwrap() {
-                "$" => {
-                    match split.next().unwrap() {
-                        "cd" => {
-                            let name = split.next().unwrap();
-                            match name {
-                                ".." => {
-                                    path.pop();
-                                }
-                                new_dir => {
-                                    path.push(new_dir);
-                                }
-                            }
-                            current = 0;
-                            current = get_element_at_path(&nodes, current, &path);
-                        }
-                        "ls" => {}
-                        _ => panic!()
-                    }
-                }
-                "dir" => {
-                    let elt = match &nodes[current] {
-                        Element::Dir { elements, .. } => {
-                            let elt_name = split.next().unwrap();
-                            if !elements.iter().any(|elt| match &nodes[*elt] {
-                                Element::Dir { name, .. } => { *name == elt_name }
-                                _ => false
-                            }) {
-                                Some(Element::Dir { name: elt_name, elements: smallvec![] })
-                            } else {
-                                None
-                            }
-                        }
-                        Element::File { .. } => { panic!(); }
-                    };
-                    if let Some(node) = elt {
-                        let idx = nodes.len();
-                        nodes.push(node);
-                        match &mut nodes[current] {
-                            Element::Dir { elements, .. } => { elements.push(idx); }
-                            Element::File { .. } => { panic!() }
-                        }
-                    }
-                }
-                size => {
-                    let elt = match &nodes[current] {
-                        Element::Dir { elements, .. } => {
-                            let elt_name = split.next().unwrap();
-                            if !elements.iter().any(|elt| match &nodes[*elt] {
-                                Element::File { name, .. } => { *name == elt_name }
-                                _ => false
-                            }) {
-                                Some(Element::File { name: elt_name, size: usize::from_str(size).unwrap() })
-                            } else {
-                                None
-                            }
-                        }
-                        Element::File { .. } => { panic!() }
-                    };
-                    if let Some(node) = elt {
-                        let idx = nodes.len();
-                        nodes.push(node);
-                        match &mut nodes[current] {
-                            Element::Dir { elements, .. } => { elements.push(idx); }
-                            Element::File { .. } => { panic!() }
-                        }
-                    }
-                }
-            }
-        }
-    }
-    nodes
-}
+const DISK_SIZE: usize = 70000000;
+const NEEDED_FREE_SPACE: usize = 30000000;
 
 #[allow(unused)]
 pub fn _p1(s: &'static str) -> usize {
-    let total_size = 0usize;
-
-    let nodes = build_file_tree(s);
-    let mut total_size = 0usize;
-    compute_dir_of_size_at_most_than_max_size_and_return_total_dir_size(&nodes, 0, 100000, &mut total_size);
-    total_size
+    let fs = VirtualFs::parse(s);
+    fs.find(ROOT, Some(NodeKind::Dir), SizeFilter::AtMost(100000)).into_iter()
+        .map(|dir| fs.size(dir))
+        .sum()
 }
 
 #[allow(unused)]
@@ -174,13 +21,8 @@ pub fn p1() -> usize {
 
 #[allow(unused)]
 pub fn _p2(s: &'static str) -> usize {
-    let nodes = build_file_tree(s);
-    let mut total_size = 0usize;
-    let total_used_space = compute_dir_of_size_at_most_than_max_size_and_return_total_dir_size(&nodes, 0, 100000, &mut total_size);
-    let bytes_to_free = 30000000 - (70000000 - total_used_space);
-    let mut smallest_large_enough = usize::MAX;
-    find_smallest_dir(&nodes, 0, bytes_to_free, &mut smallest_large_enough);
-    smallest_large_enough
+    let fs = VirtualFs::parse(s);
+    fs.size(fs.deletions_freeing(DISK_SIZE, NEEDED_FREE_SPACE)[0])
 }
 
 #[allow(unused)]
@@ -193,6 +35,7 @@ pub fn p2() -> usize {
 mod j7_tests {
     #[allow(unused)]
     use super::*;
+    use crate::j7::vfs::Inconsistency;
 
     #[test]
     #[allow(unused)]
@@ -207,4 +50,99 @@ mod j7_tests {
         assert_eq!(24933642, _p2(include_str!("j7_test.txt")));
         assert_eq!(578710, _p2(include_str!("j7.txt")));
     }
+
+    #[test]
+    #[allow(unused)]
+    fn test_virtual_fs_queries() {
+        let fs = VirtualFs::parse(include_str!("j7_test.txt"));
+        let e = fs.lookup("/a/e").unwrap();
+        assert_eq!(Some(e), fs.lookup("d/../a/./e/"));
+        assert_eq!("/a/e", fs.path(e));
+        assert_eq!(584, fs.size(e));
+        assert_eq!(14848514, fs.size(fs.lookup("/b.txt").unwrap()));
+        assert_eq!(None, fs.lookup("/a/z"));
+        assert_eq!(48381165, fs.used_space());
+
+        let big_files = fs.find(ROOT, Some(NodeKind::File), SizeFilter::AtLeast(5000000));
+        assert_eq!(vec!["/b.txt", "/c.dat", "/d/d.log", "/d/d.ext", "/d/k"], big_files.iter().map(|id| fs.path(*id)).collect::<Vec<_>>());
+        assert_eq!(vec![e, fs.lookup("/a/e/i").unwrap()], fs.find(ROOT, None, SizeFilter::Between(500, 600)));
+
+        assert_eq!("584\t/a/e\n94853\t/a\n24933642\t/d\n48381165\t/\n", fs.du_report(ROOT, None));
+        assert_eq!("94853\t/a\n", fs.du_report(fs.lookup("a").unwrap(), Some(0)));
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_virtual_fs_deletions() {
+        let mut fs = VirtualFs::parse(include_str!("j7_test.txt"));
+        let d = fs.lookup("/d").unwrap();
+        assert_eq!(vec![d, ROOT], fs.deletions_freeing(DISK_SIZE, NEEDED_FREE_SPACE));
+        assert_eq!(46552477, fs.free_space_after_deleting(DISK_SIZE, d));
+
+        // A disk already too small for the files
+        let e = fs.lookup("/a/e").unwrap();
+        assert_eq!(16552477, fs.free_space_after_deleting(40000000, d));
+        assert_eq!(0, fs.free_space_after_deleting(40000000, e));
+        assert_eq!(vec![d, ROOT], fs.deletions_freeing(40000000, 10000000));
+        assert_eq!(vec![ROOT], fs.deletions_freeing(10000000, 0));
+
+        fs.delete(d);
+        assert_eq!(None, fs.lookup("/d"));
+        assert_eq!(23447523, fs.used_space());
+        assert_eq!(vec![ROOT], fs.find(ROOT, Some(NodeKind::Dir), SizeFilter::AtLeast(1000000)));
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_export() {
+        let fs = export::replay(include_str!("j7_test.txt")).unwrap();
+        assert_eq!(concat!(
+            "- / (dir)\n",
+            "  - a (dir)\n",
+            "    - e (dir)\n",
+            "      - i (file, size=584)\n",
+            "    - f (file, size=29116)\n",
+            "    - g (file, size=2557)\n",
+            "    - h.lst (file, size=62596)\n",
+            "  - b.txt (file, size=14848514)\n",
+            "  - c.dat (file, size=8504156)\n",
+            "  - d (dir)\n",
+            "    - d.ext (file, size=5626152)\n",
+            "    - d.log (file, size=8033020)\n",
+            "    - j (file, size=4060174)\n",
+            "    - k (file, size=7214296)\n"), export::tree_listing(&fs));
+
+        let json = export::to_json(&fs);
+        assert!(json.starts_with(r#"{"name":"/","type":"dir","size":48381165,"children":[{"name":"a","type":"dir","size":94853,"children":[{"name":"e""#));
+        assert!(json.ends_with(r#"{"name":"k","type":"file","size":7214296}]}]}"#));
+
+        let script = export::to_shell_script(&fs);
+        assert!(script.contains("mkdir -p \"$root\"/'a/e'\ntruncate -s 584 \"$root\"/'a/e/i'\n"));
+        assert_eq!(14, script.lines().filter(|line| line.starts_with("mkdir") || line.starts_with("truncate")).count());
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_export_inconsistent_log() {
+        let log = "$ cd /\n$ ls\n10 x\ndir y\n$ ls\n12 x\n5 y\n$ cd x\n";
+        let fs = VirtualFs::parse(log);
+        assert_eq!(10, fs.used_space());
+        let error = export::replay(log).unwrap_err();
+        assert_eq!(vec![
+            Inconsistency::FileSizeMismatch { line: 6, path: "/x".to_string(), listed: 12, previous: 10 },
+            Inconsistency::KindMismatch { line: 7, path: "/y".to_string(), listed_as_dir: false },
+            Inconsistency::KindMismatch { line: 8, path: "/x".to_string(), listed_as_dir: true },
+        ], error.0);
+        assert_eq!("line 6: /x listed with size 12 but was 10", error.0[0].to_string());
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_virtual_fs_cd_root() {
+        let fs = VirtualFs::parse("$ cd /\n$ cd a\n$ ls\n10 x\n$ cd /\n$ ls\ndir a\n5 y\n$ cd a\n$ cd b\n$ ls\n7 z\n");
+        assert_eq!(22, fs.used_space());
+        assert_eq!(17, fs.size(fs.lookup("/a").unwrap()));
+        assert_eq!(Some(7), fs.lookup("/a/b/z").map(|id| fs.size(id)));
+        assert_eq!(2, fs.children(ROOT).len());
+    }
 }
\ No newline at end of file
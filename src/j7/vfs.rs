@@ -0,0 +1,342 @@
+use std::cell::OnceCell;
+use std::fmt::{Display, Formatter, Write};
+use std::str::FromStr;
+
+pub type NodeId = usize;
+
+pub const ROOT: NodeId = 0;
+
+#[allow(unused)]
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum Node<'a> {
+    Dir { name: &'a str, parent: Option<NodeId>, children: Vec<NodeId> },
+    File { name: &'a str, parent: Option<NodeId>, size: usize },
+}
+
+#[allow(unused)]
+impl<'a> Node<'a> {
+    pub fn name(&self) -> &'a str {
+        match self {
+            Node::Dir { name, .. } | Node::File { name, .. } => name,
+        }
+    }
+
+    pub fn parent(&self) -> Option<NodeId> {
+        match self {
+            Node::Dir { parent, .. } | Node::File { parent, .. } => *parent,
+        }
+    }
+
+    pub fn is_dir(&self) -> bool {
+        matches!(self, Node::Dir { .. })
+    }
+}
+
+#[allow(unused)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum NodeKind {
+    Dir,
+    File,
+}
+
+#[allow(unused)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SizeFilter {
+    Any,
+    AtMost(usize),
+    AtLeast(usize),
+    Between(usize, usize),
+}
+
+#[allow(unused)]
+impl SizeFilter {
+    pub fn matches(&self, size: usize) -> bool {
+        match *self {
+            SizeFilter::Any => true,
+            SizeFilter::AtMost(max) => size <= max,
+            SizeFilter::AtLeast(min) => size >= min,
+            SizeFilter::Between(min, max) => min <= size && size <= max,
+        }
+    }
+}
+
+// Log lines contradicting what was listed before, `line` is 1 based
+#[allow(unused)]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Inconsistency {
+    FileSizeMismatch { line: usize, path: String, listed: usize, previous: usize },
+    KindMismatch { line: usize, path: String, listed_as_dir: bool },
+}
+
+impl Display for Inconsistency {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Inconsistency::FileSizeMismatch { line, path, listed, previous } =>
+                write!(f, "line {line}: {path} listed with size {listed} but was {previous}"),
+            Inconsistency::KindMismatch { line, path, listed_as_dir: true } =>
+                write!(f, "line {line}: {path} listed as a directory but is a file"),
+            Inconsistency::KindMismatch { line, path, listed_as_dir: false } =>
+                write!(f, "line {line}: {path} listed as a file but is a directory"),
+        }
+    }
+}
+
+// Filesystem rebuilt from a terminal session, nodes live in an arena and a
+// child always has a greater id than its parent
+#[allow(unused)]
+#[derive(Debug, Clone)]
+pub struct VirtualFs<'a> {
+    nodes: Vec<Node<'a>>,
+    sizes: OnceCell<Vec<usize>>,
+    inconsistencies: Vec<Inconsistency>,
+}
+
+#[allow(unused)]
+impl<'a> VirtualFs<'a> {
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![Node::Dir { name: "/", parent: None, children: vec![] }],
+            sizes: OnceCell::new(),
+            inconsistencies: vec![],
+        }
+    }
+
+    pub fn parse(s: &'a str) -> Self {
+        let mut fs = Self::new();
+        let mut current = ROOT;
+
+        for (line_number, line) in s.lines().enumerate() {
+            let mut split = line.split(' ');
+
+            match split.next().unwrap() {
+                "$" => {
+                    match split.next().unwrap() {
+                        "cd" => {
+                            current = match split.next().unwrap() {
+                                "/" => ROOT,
+                                ".." => fs.nodes[current].parent().unwrap_or(ROOT),
+                                name => match fs.child(current, name) {
+                                    None => fs.add_dir(current, name),
+                                    Some(child) if fs.nodes[child].is_dir() => child,
+                                    Some(child) => {
+                                        let path = fs.path(child);
+                                        fs.inconsistencies.push(Inconsistency::KindMismatch { line: line_number + 1, path, listed_as_dir: true });
+                                        current
+                                    }
+                                },
+                            };
+                        }
+                        "ls" => {}
+                        _ => panic!()
+                    }
+                }
+                "dir" => {
+                    let name = split.next().unwrap();
+                    match fs.child(current, name) {
+                        None => { fs.add_dir(current, name); }
+                        Some(child) if !fs.nodes[child].is_dir() => {
+                            let path = fs.path(child);
+                            fs.inconsistencies.push(Inconsistency::KindMismatch { line: line_number + 1, path, listed_as_dir: true });
+                        }
+                        Some(_) => {}
+                    }
+                }
+                size => {
+                    let name = split.next().unwrap();
+                    let size = usize::from_str(size).unwrap();
+                    match fs.child(current, name).map(|child| (child, &fs.nodes[child])) {
+                        None => { fs.add_file(current, name, size); }
+                        Some((child, Node::Dir { .. })) => {
+                            let path = fs.path(child);
+                            fs.inconsistencies.push(Inconsistency::KindMismatch { line: line_number + 1, path, listed_as_dir: false });
+                        }
+                        Some((child, Node::File { size: previous, .. })) if *previous != size => {
+                            let previous = *previous;
+                            let path = fs.path(child);
+                            fs.inconsistencies.push(Inconsistency::FileSizeMismatch { line: line_number + 1, path, listed: size, previous });
+                        }
+                        Some(_) => {}
+                    }
+                }
+            }
+        }
+        fs
+    }
+
+    // Contradictions found while parsing, the first listing always wins
+    pub fn inconsistencies(&self) -> &[Inconsistency] {
+        &self.inconsistencies
+    }
+
+    fn push(&mut self, parent: NodeId, node: Node<'a>) -> NodeId {
+        let id = self.nodes.len();
+        self.nodes.push(node);
+        match &mut self.nodes[parent] {
+            Node::Dir { children, .. } => children.push(id),
+            Node::File { .. } => panic!("{} is not a directory", self.path(parent)),
+        }
+        self.sizes.take();
+        id
+    }
+
+    pub fn add_dir(&mut self, parent: NodeId, name: &'a str) -> NodeId {
+        self.push(parent, Node::Dir { name, parent: Some(parent), children: vec![] })
+    }
+
+    pub fn add_file(&mut self, parent: NodeId, name: &'a str, size: usize) -> NodeId {
+        self.push(parent, Node::File { name, parent: Some(parent), size })
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node<'a> {
+        &self.nodes[id]
+    }
+
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        match &self.nodes[id] {
+            Node::Dir { children, .. } => children,
+            Node::File { .. } => &[],
+        }
+    }
+
+    pub fn child(&self, id: NodeId, name: &str) -> Option<NodeId> {
+        self.children(id).iter().copied().find(|child| self.nodes[*child].name() == name)
+    }
+
+    // Absolute or root relative path, `.` and `..` are supported
+    pub fn lookup(&self, path: &str) -> Option<NodeId> {
+        let mut current = ROOT;
+        for part in path.split('/') {
+            current = match part {
+                "" | "." => current,
+                ".." => self.nodes[current].parent().unwrap_or(ROOT),
+                name => self.child(current, name)?,
+            };
+        }
+        Some(current)
+    }
+
+    pub fn path(&self, id: NodeId) -> String {
+        let mut parts = vec![];
+        let mut current = id;
+        while let Some(parent) = self.nodes[current].parent() {
+            parts.push(self.nodes[current].name());
+            current = parent;
+        }
+        let mut path = String::new();
+        for part in parts.iter().rev() {
+            path.push('/');
+            path.push_str(part);
+        }
+        if path.is_empty() {
+            path.push('/');
+        }
+        path
+    }
+
+    fn sizes(&self) -> &Vec<usize> {
+        self.sizes.get_or_init(|| {
+            let mut sizes = vec![0usize; self.nodes.len()];
+            for (id, node) in self.nodes.iter().enumerate().rev() {
+                if let Node::File { size, .. } = node {
+                    sizes[id] = *size;
+                }
+                if let Some(parent) = node.parent() {
+                    sizes[parent] += sizes[id];
+                }
+            }
+            sizes
+        })
+    }
+
+    // Total size of a file or of everything below a directory
+    pub fn size(&self, id: NodeId) -> usize {
+        self.sizes()[id]
+    }
+
+    pub fn used_space(&self) -> usize {
+        self.size(ROOT)
+    }
+
+    // Every node reachable from `start` (included), parents before children
+    pub fn walk(&self, start: NodeId) -> Vec<NodeId> {
+        let mut result = vec![];
+        let mut stack = vec![start];
+        while let Some(id) = stack.pop() {
+            result.push(id);
+            stack.extend(self.children(id).iter().rev());
+        }
+        result
+    }
+
+    pub fn find(&self, start: NodeId, kind: Option<NodeKind>, filter: SizeFilter) -> Vec<NodeId> {
+        self.walk(start).into_iter()
+            .filter(|id| match kind {
+                None => true,
+                Some(NodeKind::Dir) => self.nodes[*id].is_dir(),
+                Some(NodeKind::File) => !self.nodes[*id].is_dir(),
+            })
+            .filter(|id| filter.matches(self.size(*id)))
+            .collect()
+    }
+
+    // Like `du` : every directory below `start` up to `max_depth`, children
+    // before their parent, with its total size
+    pub fn du(&self, start: NodeId, max_depth: Option<usize>) -> Vec<(NodeId, usize)> {
+        let mut result = vec![];
+        self.du_rec(start, 0, max_depth, &mut result);
+        result
+    }
+
+    fn du_rec(&self, id: NodeId, depth: usize, max_depth: Option<usize>, result: &mut Vec<(NodeId, usize)>) {
+        if max_depth.is_none_or(|max| depth < max) {
+            for child in self.children(id) {
+                if self.nodes[*child].is_dir() {
+                    self.du_rec(*child, depth + 1, max_depth, result);
+                }
+            }
+        }
+        result.push((id, self.size(id)));
+    }
+
+    pub fn du_report(&self, start: NodeId, max_depth: Option<usize>) -> String {
+        let mut report = String::new();
+        for (id, size) in self.du(start, max_depth) {
+            writeln!(report, "{size}\t{}", self.path(id)).unwrap();
+        }
+        report
+    }
+
+    // Detaches a node and everything below it, the root cannot be deleted
+    pub fn delete(&mut self, id: NodeId) {
+        assert_ne!(ROOT, id, "cannot delete the root directory");
+        if let Some(parent) = self.nodes[id].parent() {
+            if let Node::Dir { children, .. } = &mut self.nodes[parent] {
+                children.retain(|child| *child != id);
+            }
+        }
+        match &mut self.nodes[id] {
+            Node::Dir { parent, .. } | Node::File { parent, .. } => *parent = None,
+        }
+        self.sizes.take();
+    }
+
+    // 0 when the other files do not fit on the disk either
+    pub fn free_space_after_deleting(&self, disk_size: usize, id: NodeId) -> usize {
+        (disk_size + self.size(id)).saturating_sub(self.used_space())
+    }
+
+    // Directories whose deletion would leave at least `needed` free bytes,
+    // smallest first
+    pub fn deletions_freeing(&self, disk_size: usize, needed: usize) -> Vec<NodeId> {
+        let to_free = (needed + self.used_space()).saturating_sub(disk_size);
+        let mut dirs = self.find(ROOT, Some(NodeKind::Dir), SizeFilter::AtLeast(to_free));
+        dirs.sort_by_key(|id| self.size(*id));
+        dirs
+    }
+}
+
+impl Default for VirtualFs<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
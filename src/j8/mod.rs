@@ -1,102 +1,76 @@
-use std::ops::Index;
+use crate::grid::{Grid, NEIGHBORS_4, NEIGHBORS_8, Point};
 
-use smallvec::SmallVec;
+pub const ORTHOGONAL: &[(isize, isize)] = &NEIGHBORS_4;
+#[allow(unused)]
+pub const ALL_DIRECTIONS: &[(isize, isize)] = &NEIGHBORS_8;
 
-#[derive(Default, Clone, Debug)]
-struct Matrix {
-    inner: SmallVec<[u8; 128 * 128]>,
-    rows: usize,
-    cols: usize,
+#[inline(always)]
+pub fn parse_matrix(s: &str) -> Grid<u8> {
+    Grid::parse(s, |c| c.to_digit(10).map(|d| d as u8)).unwrap()
 }
 
-impl Matrix {
-    #[inline(always)]
-    pub fn push(&mut self, c: u8) {
-        self.inner.push(c);
-    }
-
-    #[inline(always)]
-    pub fn end_row(&mut self) {
-        self.rows += 1;
-        self.cols = if self.rows == 1 { self.inner.len() } else if self.inner.len() % self.cols != 0 { panic!() } else { self.cols }
-    }
-
-    #[inline(always)]
-    pub fn cols(&self) -> usize {
-        self.cols
-    }
-
-    #[inline(always)]
-    pub fn rows(&self) -> usize {
-        self.rows
+// Walks every line of trees parallel to `direction`, starting from the edge
+// the direction points to, with a monotonic stack of the trees already seen.
+// For each tree, gives the distance to the first tree at least as tall in
+// that direction (None if it sees the edge) and the distance to the edge
+#[inline(always)]
+fn sweep(trees: &Grid<u8>, direction: (isize, isize), mut visit: impl FnMut(Point, Option<usize>, usize)) {
+    let back = (-direction.0, -direction.1);
+    let mut stack: Vec<(u8, usize)> = Vec::with_capacity(trees.rows().max(trees.cols()));
+    for start in trees.points().filter(|p| trees.offset(*p, direction).is_none()) {
+        stack.clear();
+        let mut current = Some(start);
+        let mut distance_to_edge = 0;
+        while let Some(point) = current {
+            let tree_height = trees[point];
+            while stack.last().is_some_and(|(height, _)| *height < tree_height) {
+                stack.pop();
+            }
+            visit(point, stack.last().map(|(_, blocking)| distance_to_edge - blocking), distance_to_edge);
+            stack.push((tree_height, distance_to_edge));
+            distance_to_edge += 1;
+            current = trees.offset(point, back);
+        }
     }
 }
 
-impl Index<usize> for Matrix {
-    type Output = [u8];
-
-    #[inline(always)]
-    fn index(&self, index: usize) -> &Self::Output {
-        let row = index % self.cols;
-        &self.inner[(row * self.cols)..((row + 1) * self.cols)]
+#[allow(unused)]
+pub fn visibility(trees: &Grid<u8>, directions: &[(isize, isize)]) -> Grid<bool> {
+    let mut visible = Grid::new(trees.rows(), trees.cols(), false);
+    for direction in directions {
+        sweep(trees, *direction, |point, blocked_at, _| {
+            if blocked_at.is_none() {
+                visible[point] = true;
+            }
+        });
     }
+    visible
 }
 
-impl Index<(usize, usize)> for Matrix {
-    type Output = u8;
-
-    #[inline(always)]
-    fn index(&self, (row, col): (usize, usize)) -> &Self::Output {
-        &self.inner[row * self.cols + col]
+#[allow(unused)]
+pub fn scenic_scores(trees: &Grid<u8>, directions: &[(isize, isize)]) -> Grid<usize> {
+    let mut scores = Grid::new(trees.rows(), trees.cols(), 1usize);
+    for direction in directions {
+        sweep(trees, *direction, |point, blocked_at, distance_to_edge| {
+            scores[point] *= blocked_at.unwrap_or(distance_to_edge);
+        });
     }
+    scores
 }
 
-#[inline(always)]
-fn parse_matrix(s: &str) -> Matrix {
-    let mut matrix = Matrix::default();
-
-    for line in s.lines() {
-        for c in line.chars() {
-            matrix.push(c as u8 - b'0');
-        }
-        matrix.end_row();
-    }
-
-    matrix
+// Highest scenic score and where it is, the first one in reading order on ties
+#[allow(unused)]
+pub fn best_tree(trees: &Grid<u8>, directions: &[(isize, isize)]) -> Option<(Point, usize)> {
+    scenic_scores(trees, directions).enumerate()
+        .fold(None, |best, (point, score)| match best {
+            Some((_, best_score)) if best_score >= *score => best,
+            _ => Some((point, *score)),
+        })
 }
 
 #[allow(unused)]
 pub fn _p1(s: &str) -> usize {
-    let trees = parse_matrix(s);
-
-    let height = trees.rows();
-    let width = trees.cols();
-
-    let mut total = 0;
-
-    for row in 0..height {
-        for col in 0..width {
-            let tree_height = trees[(row, col)];
-            if ((row + 1)..height).all(|l| trees[(l, col)] < tree_height) {
-                total += 1;
-                continue;
-            }
-            if (0..row).all(|l| trees[(l, col)] < tree_height) {
-                total += 1;
-                continue;
-            }
-            if ((col + 1)..width).all(|l| trees[(row, l)] < tree_height) {
-                total += 1;
-                continue;
-            }
-            if (0..col).all(|l| trees[(row, l)] < tree_height) {
-                total += 1;
-                continue;
-            }
-        }
-    }
-
-    total
+    visibility(&parse_matrix(s), ORTHOGONAL).iter().filter(|visible| **visible).count()
 }
 
 #[allow(unused)]
@@ -108,8 +82,8 @@ pub fn p1() -> usize {
 pub fn _p2(s: &str) -> usize {
     let trees = parse_matrix(s);
 
-    let height = trees.rows;
-    let width = trees.cols;
+    let height = trees.rows();
+    let width = trees.cols();
 
     let mut best_viewing_distance = 0;
 
@@ -159,4 +133,39 @@ mod j8_tests {
         assert_eq!(16, _p2(include_str!("j8_test.txt")));
         assert_eq!(535680, _p2(include_str!("j8.txt")));
     }
+
+    fn naive_viewing_distance(trees: &Grid<u8>, point: Point, direction: (isize, isize)) -> (bool, usize) {
+        let mut distance = 0;
+        let mut current = point;
+        while let Some(next) = trees.offset(current, direction) {
+            distance += 1;
+            if trees[next] >= trees[point] {
+                return (false, distance);
+            }
+            current = next;
+        }
+        (true, distance)
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_matrices() {
+        let trees = parse_matrix(include_str!("j8_test.txt"));
+        assert_eq!("#####\n###.#\n##.##\n#.#.#\n#####\n", visibility(&trees, ORTHOGONAL).render(|v| if *v { '#' } else { '.' }));
+        assert_eq!(vec![0, 0, 0, 0, 0, 0, 1, 4, 1, 0, 0, 6, 1, 2, 0, 0, 1, 8, 3, 0, 0, 0, 0, 0, 0],
+                   scenic_scores(&trees, ORTHOGONAL).iter().copied().collect::<Vec<_>>());
+        assert_eq!(Some(((3, 2), 8)), best_tree(&trees, ORTHOGONAL));
+        assert_eq!(535680, best_tree(&parse_matrix(include_str!("j8.txt")), ORTHOGONAL).unwrap().1);
+
+        for input in [include_str!("j8_test.txt"), include_str!("j8.txt")] {
+            let trees = parse_matrix(input);
+            let visible = visibility(&trees, ALL_DIRECTIONS);
+            let scores = scenic_scores(&trees, ALL_DIRECTIONS);
+            for point in trees.points() {
+                let views = ALL_DIRECTIONS.iter().map(|d| naive_viewing_distance(&trees, point, *d)).collect::<Vec<_>>();
+                assert_eq!(views.iter().any(|(edge, _)| *edge), visible[point]);
+                assert_eq!(views.iter().map(|(_, distance)| distance).product::<usize>(), scores[point]);
+            }
+        }
+    }
 }
\ No newline at end of file
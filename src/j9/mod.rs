@@ -1,10 +1,10 @@
+use std::collections::HashSet;
+use std::iter::Peekable;
 use std::ops::{AddAssign, Range, Sub};
 use std::str::FromStr;
 
-use smallvec::{SmallVec, smallvec};
-
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
-struct Vec2D((i32, i32));
+pub struct Vec2D(pub (i32, i32));
 
 impl AddAssign for Vec2D {
     #[inline(always)]
@@ -30,23 +30,6 @@ impl From<(i32, i32)> for Vec2D {
     }
 }
 
-#[allow(unused)]
-fn print_grid(range: Range<i32>, tail_pos: Vec2D, head_pos: Vec2D) {
-    for row in range.clone() {
-        for col in range.clone() {
-            if head_pos == (row, col).into() {
-                print!("H");
-            } else if tail_pos == (row, col).into() {
-                print!("T");
-            } else {
-                print!("_");
-            }
-        }
-        println!()
-    }
-    println!()
-}
-
 #[inline(always)]
 fn get_movement_direction(direction: char) -> Vec2D {
     match direction {
@@ -58,74 +41,175 @@ fn get_movement_direction(direction: char) -> Vec2D {
     }.into()
 }
 
-pub fn solve<const ROPE_LENGTH: usize,
-    const EXPECTED_MAX_TAIL_MOVEMENTS: usize,
-    const EXPECTED_VIRTUAL_GRID_CELLS: usize>(s: &str) -> usize {
-    let mut found_positions: SmallVec<[Vec2D; EXPECTED_MAX_TAIL_MOVEMENTS]> = smallvec![];
-
-    let mut knots: [Vec2D; ROPE_LENGTH] = [(0i32, 0i32).into(); ROPE_LENGTH];
-    let tail_pos = knots[knots.len() - 1];
-    found_positions.push(tail_pos);
-    for line in s.lines() {
+#[inline(always)]
+fn parse_moves(s: &str) -> impl Iterator<Item=(Vec2D, usize)> + '_ {
+    s.lines().map(|line| {
         let mut chars = line.split(' ');
         let direction = chars.next().unwrap().chars().next().unwrap();
         let steps = usize::from_str(chars.next().unwrap()).unwrap();
-        let movement = get_movement_direction(direction);
-
-        'outer: for _ in 0..steps {
-            knots[0] += movement;
-            for i in 0..(ROPE_LENGTH - 1) {
-                let diff = knots[i] - knots[i + 1];
-                if diff.0.0.abs() <= 1 && diff.0.1.abs() <= 1 {
-                    continue 'outer;
-                }
-                knots[i + 1] = match diff.0 {
-                    (2, -2) => knots[i] - (1, -1).into(),
-                    (-2, -2) => knots[i] - (-1, -1).into(),
-                    (-2, 2) => knots[i] - (-1, 1).into(),
-                    (2, 2) => knots[i] - (1, 1).into(),
-                    (2, _) => knots[i] - (1, 0).into(),
-                    (-2, _) => knots[i] - (-1, 0).into(),
-                    (_, 2) => knots[i] - (0, 1).into(),
-                    (_, -2) => knots[i] - (0, -1).into(),
-                    _ => {
-                        dbg!(diff);
-                        panic!()
-                    }
+        (get_movement_direction(direction), steps)
+    })
+}
+
+#[allow(unused)]
+#[derive(Clone, Debug)]
+pub struct Rope {
+    knots: Vec<Vec2D>,
+    visited: HashSet<Vec2D>,
+    trajectories: Option<Vec<Vec<Vec2D>>>,
+}
+
+#[allow(unused)]
+impl Rope {
+    pub fn new(length: usize) -> Self {
+        assert!(length > 0, "a rope needs at least one knot");
+        let knots = vec![Vec2D((0, 0)); length];
+        let mut visited = HashSet::new();
+        visited.insert(knots[length - 1]);
+        Self { knots, visited, trajectories: None }
+    }
+
+    // Also keeps every position of every knot, starting with the initial one
+    pub fn with_trajectories(length: usize) -> Self {
+        let mut rope = Self::new(length);
+        rope.trajectories = Some(rope.knots.iter().map(|knot| vec![*knot]).collect());
+        rope
+    }
+
+    pub fn knots(&self) -> &[Vec2D] {
+        &self.knots
+    }
+
+    pub fn tail(&self) -> Vec2D {
+        self.knots[self.knots.len() - 1]
+    }
+
+    pub fn visited(&self) -> &HashSet<Vec2D> {
+        &self.visited
+    }
+
+    pub fn trajectory(&self, knot: usize) -> Option<&[Vec2D]> {
+        self.trajectories.as_ref().map(|trajectories| trajectories[knot].as_slice())
+    }
+
+    // Moves the head by one cell, the other knots follow
+    #[inline(always)]
+    pub fn step(&mut self, movement: Vec2D) {
+        self.knots[0] += movement;
+        let mut moved = 1;
+        for i in 0..(self.knots.len() - 1) {
+            let diff = self.knots[i] - self.knots[i + 1];
+            if diff.0.0.abs() <= 1 && diff.0.1.abs() <= 1 {
+                break;
+            }
+            self.knots[i + 1] += (diff.0.0.signum(), diff.0.1.signum()).into();
+            moved += 1;
+        }
+        if moved == self.knots.len() {
+            self.visited.insert(self.tail());
+        }
+        if let Some(trajectories) = self.trajectories.as_mut() {
+            for (trajectory, knot) in trajectories.iter_mut().zip(self.knots.iter()) {
+                trajectory.push(*knot);
+            }
+        }
+    }
+
+    pub fn apply(&mut self, movement: Vec2D, steps: usize) {
+        for _ in 0..steps {
+            self.step(movement);
+        }
+    }
+
+    pub fn run(&mut self, s: &str) {
+        for (movement, steps) in parse_moves(s) {
+            self.apply(movement, steps);
+        }
+    }
+}
+
+#[allow(unused)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Frame {
+    pub step: usize,
+    pub knots: Vec<Vec2D>,
+}
+
+#[allow(unused)]
+impl Frame {
+    // Same drawing as the puzzle : `H` for the head, then the knots numbers
+    // (`T` for a two knots rope), `s` for the start and up is the first line
+    pub fn render(&self, rows: Range<i32>, cols: Range<i32>) -> String {
+        let mut drawing = String::new();
+        for row in rows.rev() {
+            for col in cols.clone() {
+                let cell = Vec2D((row, col));
+                let c = match self.knots.iter().position(|knot| *knot == cell) {
+                    Some(0) => 'H',
+                    Some(_) if self.knots.len() == 2 => 'T',
+                    Some(i) => char::from_digit(i as u32, 36).unwrap_or('#'),
+                    None if cell == Vec2D((0, 0)) => 's',
+                    None => '.',
                 };
+                drawing.push(c);
             }
-            let tail_pos = knots[knots.len() - 1];
-            found_positions.push(tail_pos);
+            drawing.push('\n');
         }
+        drawing
     }
-    let mut bottom_most = 0i32;
-    let mut left_most = 0i32;
-    let mut up_most = 0i32;
-    let mut right_most = 0i32;
+}
 
-    for elt in found_positions.iter() {
-        bottom_most = bottom_most.min(elt.0.0);
-        left_most = left_most.min(elt.0.1);
-        up_most = up_most.max(elt.0.0);
-        right_most = right_most.max(elt.0.1);
+// Every state of the rope, the initial one included
+#[allow(unused)]
+pub struct Frames<I: Iterator<Item=(Vec2D, usize)>> {
+    rope: Rope,
+    moves: Peekable<I>,
+    remaining: usize,
+    step: usize,
+}
+
+#[allow(unused)]
+pub fn frames(s: &str, length: usize) -> Frames<impl Iterator<Item=(Vec2D, usize)> + '_> {
+    Frames {
+        rope: Rope::new(length),
+        moves: parse_moves(s).peekable(),
+        remaining: 0,
+        step: 0,
     }
-    let grid_width = right_most - left_most + 1;
+}
 
-    let mut grid = SmallVec::from([false; EXPECTED_VIRTUAL_GRID_CELLS]);
+impl<I: Iterator<Item=(Vec2D, usize)>> Iterator for Frames<I> {
+    type Item = Frame;
 
-    for elt in found_positions.iter() {
-        let row = elt.0.0 - bottom_most;
-        let col = elt.0.1 - left_most;
-        let idx = row * grid_width + col;
-        grid[idx as usize] = true;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.step > 0 {
+            while self.remaining == 0 {
+                self.remaining = self.moves.peek()?.1;
+                if self.remaining == 0 {
+                    self.moves.next();
+                }
+            }
+            self.rope.step(self.moves.peek().unwrap().0);
+            self.remaining -= 1;
+            if self.remaining == 0 {
+                self.moves.next();
+            }
+        }
+        let frame = Frame { step: self.step, knots: self.rope.knots.clone() };
+        self.step += 1;
+        Some(frame)
     }
+}
 
-    grid.into_iter().filter(|elt| *elt).count()
+pub fn solve(s: &str, rope_length: usize) -> usize {
+    let mut rope = Rope::new(rope_length);
+    rope.run(s);
+    rope.visited().len()
 }
 
 #[allow(unused)]
 pub fn _p1(s: &str) -> usize {
-    solve::<2, 20000, 89112>(s)
+    solve(s, 2)
 }
 
 #[allow(unused)]
@@ -135,7 +219,7 @@ pub fn p1() -> usize {
 
 #[allow(unused)]
 pub fn _p2(s: &str) -> usize {
-    solve::<10, 3000, 83296>(s)
+    solve(s, 10)
 }
 
 #[allow(unused)]
@@ -163,4 +247,56 @@ mod j9_tests {
         assert_eq!(36, _p2(include_str!("j9_test2.txt")));
         assert_eq!(2643, _p2(include_str!("j9.txt")));
     }
+
+    #[test]
+    #[allow(unused)]
+    fn test_rope_trajectories() {
+        assert_eq!(6406, solve(include_str!("j9.txt"), 2));
+        assert_eq!(1, solve(include_str!("j9_test.txt"), 1000));
+
+        let mut rope = Rope::with_trajectories(3);
+        rope.run("R 2\nU 2\n");
+        assert_eq!(&[(0, 0), (0, 1), (0, 2), (1, 2), (2, 2)].map(Vec2D)[..], rope.trajectory(0).unwrap());
+        assert_eq!(&[(0, 0), (0, 0), (0, 1), (0, 1), (1, 2)].map(Vec2D)[..], rope.trajectory(1).unwrap());
+        assert_eq!(&[(0, 0), (0, 0), (0, 0), (0, 0), (1, 1)].map(Vec2D)[..], rope.trajectory(2).unwrap());
+        assert_eq!(None, Rope::new(3).trajectory(0));
+
+        let mut rope = Rope::with_trajectories(10);
+        rope.run(include_str!("j9_test2.txt"));
+        let tail = rope.trajectory(9).unwrap();
+        assert_eq!(36, tail.iter().collect::<HashSet<_>>().len());
+        assert_eq!(rope.visited().len(), 36);
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_frames() {
+        let all_frames = frames(include_str!("j9_test.txt"), 2).collect::<Vec<_>>();
+        assert_eq!(25, all_frames.len());
+        assert_eq!(concat!(
+            "......\n",
+            "......\n",
+            "......\n",
+            "......\n",
+            "H.....\n"), all_frames[0].render(0..5, 0..6));
+        assert_eq!(concat!(
+            "......\n",
+            "......\n",
+            "....H.\n",
+            "....T.\n",
+            "s.....\n"), all_frames[6].render(0..5, 0..6));
+
+        let last = frames(include_str!("j9_test2.txt"), 10).last().unwrap();
+        assert_eq!(concat!(
+            "H.........\n",
+            "1.........\n",
+            "2.........\n",
+            "3.........\n",
+            "4.........\n",
+            "5.........\n",
+            "6.........\n",
+            "7.........\n",
+            "8.........\n",
+            "9.........\n"), last.render(6..16, -11..-1));
+    }
 }
\ No newline at end of file
@@ -23,6 +23,9 @@ mod j4;
 mod j3;
 mod j2;
 mod j1;
+mod grid;
+mod search;
+mod cycle;
 
 
 use std::alloc::System;
@@ -69,7 +72,32 @@ impl AllocationTracker for StdoutTracker {
     }
 }
 
+// --j5-drawings [9000|9001] [input] : prints the j5 stacks after each move
+fn dump_j5_drawings(mut args: impl Iterator<Item=String>) {
+    let crane = args.next().unwrap_or_else(|| "9000".to_string());
+    let input = match args.next() {
+        None => include_str!("j5/j5.txt").to_string(),
+        Some(path) => std::fs::read_to_string(path).unwrap(),
+    };
+    let mut stdout = std::io::stdout();
+    let result = match crane.as_str() {
+        "9000" => j5::dump_drawings(&input, j5::crane::CrateMover9000, &mut stdout),
+        "9001" => j5::dump_drawings(&input, j5::crane::CrateMover9001, &mut stdout),
+        _ => panic!("unknown crane {crane}, expected 9000 or 9001"),
+    };
+    if let Err(e) = result {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+}
+
 fn main() {
+    let mut args = std::env::args().skip(1);
+    if let Some("--j5-drawings") = args.next().as_deref() {
+        dump_j5_drawings(args);
+        return;
+    }
+
     AllocationRegistry::set_global_tracker(StdoutTracker)
         .expect("no other global tracker should be set yet");
 
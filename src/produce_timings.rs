@@ -18,6 +18,8 @@ mod j4;
 mod j3;
 mod j2;
 mod j1;
+mod grid;
+mod search;
 
 fn main() {
     timeit!({j1::p1();});
@@ -0,0 +1,250 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use std::hash::Hash;
+
+#[allow(unused)]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct SearchStats {
+    // Nodes whose neighbors were generated
+    pub expanded: usize,
+    // Distinct nodes ever reached
+    pub discovered: usize,
+    pub max_frontier: usize,
+}
+
+// Outcome of a search, nodes are interned so that paths can be rebuilt
+// from parent links
+#[allow(unused)]
+#[derive(Clone, Debug)]
+pub struct Search<N> {
+    nodes: Vec<N>,
+    index: HashMap<N, usize>,
+    costs: Vec<usize>,
+    parents: Vec<Option<usize>>,
+    settled: Vec<bool>,
+    goal: Option<usize>,
+    stats: SearchStats,
+}
+
+#[allow(unused)]
+impl<N: Clone + Eq + Hash> Search<N> {
+    fn new() -> Self {
+        Self {
+            nodes: vec![],
+            index: HashMap::new(),
+            costs: vec![],
+            parents: vec![],
+            settled: vec![],
+            goal: None,
+            stats: SearchStats::default(),
+        }
+    }
+
+    // Returns the id of `node` and whether it was just discovered
+    fn intern(&mut self, node: N, cost: usize, parent: Option<usize>) -> (usize, bool) {
+        if let Some(idx) = self.index.get(&node) {
+            return (*idx, false);
+        }
+        let idx = self.nodes.len();
+        self.index.insert(node.clone(), idx);
+        self.nodes.push(node);
+        self.costs.push(cost);
+        self.parents.push(parent);
+        self.settled.push(false);
+        self.stats.discovered += 1;
+        (idx, true)
+    }
+
+    pub fn goal(&self) -> Option<&N> {
+        self.goal.map(|idx| &self.nodes[idx])
+    }
+
+    // Cost of the cheapest path to the goal
+    pub fn cost(&self) -> Option<usize> {
+        self.goal.map(|idx| self.costs[idx])
+    }
+
+    // Known for every node reached by a bfs, and for the nodes a dijkstra or
+    // an A* has expanded
+    pub fn cost_to(&self, node: &N) -> Option<usize> {
+        self.index.get(node).filter(|idx| self.settled[**idx]).map(|idx| self.costs[*idx])
+    }
+
+    // From one of the sources to the goal, both included
+    pub fn path(&self) -> Option<Vec<N>> {
+        self.goal.map(|idx| self.path_from_idx(idx))
+    }
+
+    pub fn path_to(&self, node: &N) -> Option<Vec<N>> {
+        self.index.get(node).filter(|idx| self.settled[**idx]).map(|idx| self.path_from_idx(*idx))
+    }
+
+    fn path_from_idx(&self, mut idx: usize) -> Vec<N> {
+        let mut path = vec![self.nodes[idx].clone()];
+        while let Some(parent) = self.parents[idx] {
+            path.push(self.nodes[parent].clone());
+            idx = parent;
+        }
+        path.reverse();
+        path
+    }
+
+    // Every node with a known cost, see `cost_to`
+    pub fn reached(&self) -> impl Iterator<Item=(&N, usize)> {
+        self.nodes.iter().zip(self.costs.iter()).zip(self.settled.iter())
+            .filter(|(_, settled)| **settled)
+            .map(|((node, cost), _)| (node, *cost))
+    }
+
+    pub fn stats(&self) -> SearchStats {
+        self.stats
+    }
+}
+
+// Breadth first search over unit cost edges, stops on the first node
+// matching `is_goal`, use `|_| false` to explore everything reachable
+#[allow(unused)]
+pub fn bfs<N, I>(sources: impl IntoIterator<Item=N>,
+                 mut neighbors: impl FnMut(&N) -> I,
+                 mut is_goal: impl FnMut(&N) -> bool) -> Search<N>
+    where N: Clone + Eq + Hash, I: IntoIterator<Item=N> {
+    let mut search = Search::new();
+    let mut frontier = VecDeque::new();
+    for source in sources {
+        let (idx, new) = search.intern(source, 0, None);
+        if new {
+            search.settled[idx] = true;
+            frontier.push_back(idx);
+        }
+    }
+
+    while let Some(idx) = frontier.pop_front() {
+        search.stats.max_frontier = search.stats.max_frontier.max(frontier.len() + 1);
+        if is_goal(&search.nodes[idx]) {
+            search.goal = Some(idx);
+            break;
+        }
+        search.stats.expanded += 1;
+        let cost = search.costs[idx] + 1;
+        for neighbor in neighbors(&search.nodes[idx]) {
+            let (neighbor_idx, new) = search.intern(neighbor, cost, Some(idx));
+            if new {
+                search.settled[neighbor_idx] = true;
+                frontier.push_back(neighbor_idx);
+            }
+        }
+    }
+    search
+}
+
+// Dijkstra over `(neighbor, cost)` edges
+#[allow(unused)]
+pub fn dijkstra<N, I>(sources: impl IntoIterator<Item=N>,
+                      neighbors: impl FnMut(&N) -> I,
+                      is_goal: impl FnMut(&N) -> bool) -> Search<N>
+    where N: Clone + Eq + Hash, I: IntoIterator<Item=(N, usize)> {
+    astar(sources, neighbors, |_| 0, is_goal)
+}
+
+// A* over `(neighbor, cost)` edges. Nodes are closed the first time they are
+// popped, so `heuristic` must be consistent for the result to be optimal :
+// never more than the cost of an edge plus the heuristic of its neighbor, and
+// 0 at the goals. Admissible but inconsistent heuristics can miss the best path
+#[allow(unused)]
+pub fn astar<N, I>(sources: impl IntoIterator<Item=N>,
+                   mut neighbors: impl FnMut(&N) -> I,
+                   mut heuristic: impl FnMut(&N) -> usize,
+                   mut is_goal: impl FnMut(&N) -> bool) -> Search<N>
+    where N: Clone + Eq + Hash, I: IntoIterator<Item=(N, usize)> {
+    let mut search = Search::new();
+    // Ties on the estimate are broken by the cheapest node, then by discovery order
+    let mut frontier = BinaryHeap::new();
+    for source in sources {
+        let (idx, new) = search.intern(source, 0, None);
+        if new {
+            frontier.push(Reverse((heuristic(&search.nodes[idx]), 0, idx)));
+        }
+    }
+
+    while let Some(Reverse((_, cost, idx))) = frontier.pop() {
+        search.stats.max_frontier = search.stats.max_frontier.max(frontier.len() + 1);
+        if search.settled[idx] || cost > search.costs[idx] {
+            continue;
+        }
+        search.settled[idx] = true;
+        if is_goal(&search.nodes[idx]) {
+            search.goal = Some(idx);
+            break;
+        }
+        search.stats.expanded += 1;
+        for (neighbor, edge_cost) in neighbors(&search.nodes[idx]) {
+            let neighbor_cost = cost + edge_cost;
+            let (neighbor_idx, new) = search.intern(neighbor, neighbor_cost, Some(idx));
+            if new || (!search.settled[neighbor_idx] && neighbor_cost < search.costs[neighbor_idx]) {
+                search.costs[neighbor_idx] = neighbor_cost;
+                search.parents[neighbor_idx] = Some(idx);
+                frontier.push(Reverse((neighbor_cost + heuristic(&search.nodes[neighbor_idx]), neighbor_cost, neighbor_idx)));
+            }
+        }
+    }
+    search
+}
+
+#[cfg(test)]
+#[allow(unused)]
+mod search_tests {
+    #[allow(unused)]
+    use super::*;
+
+    // 0 - 1 - 2 - 3 with a costly shortcut 0 -> 3 and a dead end 4
+    fn edges(node: &usize) -> Vec<(usize, usize)> {
+        match node {
+            0 => vec![(1, 1), (3, 10), (4, 1)],
+            1 => vec![(0, 1), (2, 1)],
+            2 => vec![(1, 1), (3, 1)],
+            3 => vec![(2, 1)],
+            _ => vec![],
+        }
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_bfs() {
+        let search = bfs([0], |node| edges(node).into_iter().map(|(n, _)| n), |node| *node == 3);
+        assert_eq!(Some(1), search.cost());
+        assert_eq!(Some(vec![0, 3]), search.path());
+
+        let search = bfs([0], |node| edges(node).into_iter().map(|(n, _)| n), |_| false);
+        assert_eq!(None, search.goal());
+        assert_eq!(Some(2), search.cost_to(&2));
+        assert_eq!(Some(vec![0, 1, 2]), search.path_to(&2));
+        assert_eq!(5, search.reached().count());
+        assert_eq!(5, search.stats().expanded);
+
+        let search = bfs([4, 2], |node| edges(node).into_iter().map(|(n, _)| n), |node| *node == 0);
+        assert_eq!(Some(vec![2, 1, 0]), search.path());
+    }
+
+    #[test]
+    #[allow(unused)]
+    fn test_dijkstra_and_astar() {
+        let search = dijkstra([0], edges, |node| *node == 3);
+        assert_eq!(Some(3), search.cost());
+        assert_eq!(Some(vec![0, 1, 2, 3]), search.path());
+        assert_eq!(None, search.cost_to(&5));
+
+        let search = dijkstra([0, 3], edges, |_| false);
+        assert_eq!(Some(1), search.cost_to(&2));
+        assert_eq!(Some(vec![3, 2]), search.path_to(&2));
+
+        // Exact remaining cost, only the nodes of the path are expanded
+        let search = astar([0], edges, |node| [3, 2, 1, 0, 100][*node], |node| *node == 3);
+        assert_eq!(Some(3), search.cost());
+        assert_eq!(3, search.stats().expanded);
+
+        let search = astar([0], edges, |_| 0, |node| *node == 5);
+        assert_eq!(None, search.cost());
+        assert_eq!(None, search.path());
+        assert_eq!(5, search.stats().discovered);
+    }
+}